@@ -410,13 +410,13 @@ main() {
 
     # Set channel and run install
     print_step "Setting channel to $CHANNEL and running installation"
-    if "$nockup_path" channel set "$CHANNEL" && "$nockup_path" install; then
+    if "$nockup_path" channel set "$CHANNEL" && "$nockup_path" install --no-modify-path; then
         print_success "Nockup installation completed successfully!"
     else
         print_error "Installation failed"
         print_info "You can try running manually:"
         print_info "  $nockup_path channel set $CHANNEL"
-        print_info "  $nockup_path install"
+        print_info "  $nockup_path install --no-modify-path"
         exit 1
     fi
 
@@ -17,6 +17,9 @@ GITHUB_REPO="nockchain/nockchain"
 VERSION="unknown"
 RELEASE_TAG="unknown"
 CHANNEL="stable"
+# Installation directory; nockup reads the same variable.
+NOCKUP_HOME="${NOCKUP_HOME:-$HOME/.nockup}"
+export NOCKUP_HOME
 CONFIG_URL_MACOS="https://raw.githubusercontent.com/nockchain/nockup/refs/heads/master/default-config-aarch64-apple-darwin.toml"
 CONFIG_URL_LINUX="https://raw.githubusercontent.com/nockchain/nockup/refs/heads/master/default-config-x86_64-unknown-linux-gnu.toml"
 # Determine config URL based on OS
@@ -183,7 +186,7 @@ detect_platform() {
 
 # Function to setup toolchain directory with channel manifests
 setup_toolchain() {
-    local toolchain_dir="$HOME/.nockup/toolchains"
+    local toolchain_dir="$NOCKUP_HOME/toolchains"
     
     mkdir -p "$toolchain_dir"
     
@@ -269,7 +272,7 @@ EOF
 
 # Function to setup config file
 setup_config() {
-    local config_dir="$HOME/.nockup"
+    local config_dir="$NOCKUP_HOME"
     local config_file="$config_dir/config.toml"
     
     mkdir -p "$config_dir"
@@ -295,57 +298,6 @@ EOF
     fi
 }
 
-# Function to add to PATH
-add_to_path() {
-    local bin_dir="$1"
-    local shell_rc=""
-
-    if [[ -n "${BASH_VERSION:-}" ]]; then
-        shell_rc="$HOME/.bashrc"
-    elif [[ -n "${ZSH_VERSION:-}" ]]; then
-        shell_rc="$HOME/.zshrc"
-    elif [[ "$SHELL" == *"zsh"* ]]; then
-        shell_rc="$HOME/.zshrc"
-    elif [[ "$SHELL" == *"bash"* ]]; then
-        shell_rc="$HOME/.bashrc"
-    else
-        shell_rc="$HOME/.profile"
-    fi
-
-    local path_entry="export PATH=\"$bin_dir:\$PATH\""
-    
-    if [[ -f "$shell_rc" ]] && grep -q "$path_entry" "$shell_rc" 2>/dev/null; then
-        print_info "PATH already configured in $shell_rc"
-    else
-        if [[ -w "$(dirname "$shell_rc")" ]]; then
-            touch "$shell_rc"
-            
-            echo "" >> "$shell_rc"
-            echo "# Added by nockup installer" >> "$shell_rc"
-            echo "$path_entry" >> "$shell_rc"
-            
-            print_success "Added $bin_dir to PATH in $shell_rc"
-        else
-            print_warning "Could not modify $shell_rc (permission denied)"
-            print_info "Please manually add this line to your shell configuration:"
-            print_info "  $path_entry"
-        fi
-    fi
-    
-    local activate_script="$HOME/.nockup/activate.sh"
-    cat > "$activate_script" << 'EOF'
-#!/bin/bash
-# Nockup environment activation script
-# Usage: source ~/.nockup/activate.sh
-export PATH="$HOME/.nockup/bin:$PATH"
-echo "✅ Nockup environment activated!"
-echo "📦 nockup is now available in PATH"
-EOF
-    chmod +x "$activate_script"
-    
-    print_success "Created activation script: $activate_script"
-}
-
 # Function to verify binary works
 verify_binary() {
     local binary_path="$1"
@@ -364,27 +316,15 @@ verify_binary() {
     fi
 }
 
-# Function to setup GPG key (Linux only)
-setup_gpg_key() {
-    if [[ "$(uname -s)" != "Linux" ]]; then
-        return 0
-    fi
-    
-    if ! command_exists gpg; then
-        return 0
-    fi
-    
-    local gpg_key="A6FFD2DB7D4C9710"
-    
-    if gpg --list-keys "$gpg_key" >/dev/null 2>&1; then
-        return 0
-    fi
-    
-    gpg --keyserver keyserver.ubuntu.com --recv-keys "$gpg_key" >/dev/null 2>&1 || true
-}
-
 # Main installation function
 main() {
+    local install_flags=""
+    for arg in "$@"; do
+        case "$arg" in
+            --no-modify-path) install_flags="--no-modify-path" ;;
+        esac
+    done
+
     print_step "Starting Nockup installation"
     print_info "This installer works on Linux and macOS systems"
     echo "" >&2
@@ -412,8 +352,6 @@ main() {
     setup_config
     setup_toolchain
     print_info "DEBUG: After setup_toolchain"
-    setup_gpg_key
-    print_info "DEBUG: After setup_gpg_key"
 
     local target
     target=$(detect_platform)
@@ -466,7 +404,7 @@ main() {
 
     print_success "Extracted Nockup binary"
 
-    local install_dir="$HOME/.nockup/bin"
+    local install_dir="$NOCKUP_HOME/bin"
     local nockup_path="$install_dir/nockup"
     
     print_step "Installing Nockup binary"
@@ -479,16 +417,14 @@ main() {
 
     verify_binary "$nockup_path"
 
-    add_to_path "$install_dir"
-
     print_step "Setting channel to $CHANNEL and running installation"
-    if "$nockup_path" channel set "$CHANNEL" && "$nockup_path" install; then
+    if "$nockup_path" channel set "$CHANNEL" && "$nockup_path" install $install_flags; then
         print_success "Nockup installation completed successfully!"
     else
         print_error "Installation failed"
         print_info "You can try running manually:"
         print_info "  $nockup_path channel set $CHANNEL"
-        print_info "  $nockup_path install"
+        print_info "  $nockup_path install $install_flags"
         exit 1
     fi
 
@@ -499,13 +435,13 @@ main() {
     echo -e "${YELLOW}═══════════════════════════════════════════════════════════════${NC}" >&2
     echo -e "${GREEN}To use nockup immediately in this terminal, run:${NC}" >&2
     echo "" >&2
-    echo -e "  ${CYAN}export PATH=\"$install_dir:\$PATH\"${NC}" >&2
+    echo -e "  ${CYAN}. \"$NOCKUP_HOME/env\"${NC}" >&2
     echo "" >&2
     echo -e "${YELLOW}═══════════════════════════════════════════════════════════════${NC}" >&2
     echo "" >&2
     
     print_info "Next steps:"
-    print_info "  1. Run the export command above, OR restart your shell"
+    print_info "  1. Run the command above, OR restart your shell"
     print_info "  2. Verify installation: nockup --help"
     print_info "  3. Create a project: nockup start <project-name>"
     echo "" >&2
@@ -0,0 +1,318 @@
+//! Channels and the release index they are discovered from.
+//!
+//! Every build is published as a `<channel>-build-<commit>` release, so the
+//! channels available, and the builds in each, come from the release listing
+//! rather than a fixed list.  A channel spec names either the latest build of
+//! a channel (`stable`), the build published on a given day
+//! (`stable-2025-09-01`), or an exact build (`stable@<commit>`).
+
+use std::fmt;
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Deserialize;
+
+use crate::commands::common::Mirrors;
+use crate::github;
+
+/// Separator between the channel and commit in release tags.
+const BUILD_SEPARATOR: &str = "-build-";
+
+/// Shortest commit prefix accepted in a `<channel>@<commit>` spec.
+const MIN_COMMIT_PREFIX: usize = 7;
+
+/// Releases requested per page of the listing, the most GitHub allows.
+const PER_PAGE: usize = 100;
+
+/// Most pages of the listing read, so a mirror that always offers another
+/// page cannot keep nockup paging forever.
+const MAX_PAGES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelSpec {
+    pub channel: String,
+    pub pin: Option<Pin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pin {
+    /// The latest build published on this day (`YYYY-MM-DD`).
+    Date(String),
+    /// A full commit hash or a unique prefix of one.
+    Commit(String),
+}
+
+impl ChannelSpec {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid channel: {}", spec);
+
+        if let Some((channel, commit)) = spec.split_once('@') {
+            if !is_channel_name(channel)
+                || commit.len() < MIN_COMMIT_PREFIX
+                || commit.len() > 40
+                || !commit.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(invalid());
+            }
+            return Ok(ChannelSpec {
+                channel: channel.to_string(),
+                pin: Some(Pin::Commit(commit.to_ascii_lowercase())),
+            });
+        }
+
+        if let Some((channel, date)) = split_date(spec) {
+            if !is_channel_name(channel) {
+                return Err(invalid());
+            }
+            return Ok(ChannelSpec {
+                channel: channel.to_string(),
+                pin: Some(Pin::Date(date.to_string())),
+            });
+        }
+
+        if !is_channel_name(spec) {
+            return Err(invalid());
+        }
+        Ok(ChannelSpec {
+            channel: spec.to_string(),
+            pin: None,
+        })
+    }
+
+    /// The full commit this spec pins, if it can be known without the index.
+    pub fn full_commit(&self) -> Option<&str> {
+        match &self.pin {
+            Some(Pin::Commit(commit)) if commit.len() == 40 => Some(commit),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChannelSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.pin {
+            None => write!(f, "{}", self.channel),
+            Some(Pin::Date(date)) => write!(f, "{}-{}", self.channel, date),
+            Some(Pin::Commit(commit)) => write!(f, "{}@{}", self.channel, commit),
+        }
+    }
+}
+
+/// Channel names are lowercase words joined by `-`, and may not themselves
+/// look like a dated spec or a release tag.
+fn is_channel_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.split('-').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        })
+        && !name.contains(BUILD_SEPARATOR)
+        && split_date(name).is_none()
+}
+
+/// Split `<channel>-YYYY-MM-DD` into the channel and date.
+fn split_date(spec: &str) -> Option<(&str, &str)> {
+    let split = spec.len().checked_sub(11)?;
+    if !spec.is_char_boundary(split) || spec.as_bytes()[split] != b'-' {
+        return None;
+    }
+    let (channel, date) = (&spec[..split], &spec[split + 1..]);
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some((channel, date))
+}
+
+/// A build listed in the release index.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub channel: String,
+    pub commit: String,
+    /// Day the release was published (`YYYY-MM-DD`).
+    pub date: String,
+}
+
+#[derive(Clone, Deserialize)]
+struct ReleaseEntry {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    published_at: Option<String>,
+}
+
+/// Published builds, newest first.
+pub struct ReleaseIndex {
+    releases: Vec<Release>,
+}
+
+impl ReleaseIndex {
+    /// Fetch the release listing from the configured API endpoint, following
+    /// its next-page links for up to `MAX_PAGES` pages.
+    pub async fn fetch() -> Result<Self> {
+        Self::fetch_until(|_| false).await
+    }
+
+    /// Fetch only as many pages of the listing as it takes to settle which
+    /// builds `specs` select.
+    pub async fn fetch_for(specs: &[ChannelSpec]) -> Result<Self> {
+        Self::fetch_until(|index| specs.iter().all(|spec| index.settles(spec))).await
+    }
+
+    async fn fetch_until(done: impl Fn(&ReleaseIndex) -> bool) -> Result<Self> {
+        let api = Mirrors::load().api;
+        let mut next = Some(format!("{}/releases?per_page={}&page=1", api, PER_PAGE));
+        let mut entries = Vec::new();
+        let mut pages = 0;
+        while let Some(url) = next {
+            if pages == MAX_PAGES {
+                println!(
+                    "{} Only the first {} pages of the release index were read; older builds are not listed",
+                    "⚠️".yellow(),
+                    MAX_PAGES
+                );
+                break;
+            }
+            let (page, link): (Vec<ReleaseEntry>, _) =
+                github::get_json_page(&url, "releases").await?;
+            entries.extend(page);
+            pages += 1;
+            next = link;
+            if next.is_some() && done(&Self::from_entries(entries.clone())) {
+                break;
+            }
+        }
+        Ok(Self::from_entries(entries))
+    }
+
+    fn from_entries(entries: Vec<ReleaseEntry>) -> Self {
+        let mut releases: Vec<Release> = entries
+            .into_iter()
+            .filter(|entry| !entry.draft)
+            .filter_map(|entry| {
+                let (channel, commit) = entry.tag_name.rsplit_once(BUILD_SEPARATOR)?;
+                if !is_channel_name(channel)
+                    || commit.len() != 40
+                    || !commit.chars().all(|c| c.is_ascii_hexdigit())
+                {
+                    return None;
+                }
+                let date = entry
+                    .published_at
+                    .as_deref()
+                    .and_then(|published| published.get(..10))
+                    .unwrap_or_default()
+                    .to_string();
+                Some(Release {
+                    channel: channel.to_string(),
+                    commit: commit.to_ascii_lowercase(),
+                    date,
+                })
+            })
+            .collect();
+        // Stable sort, so builds published the same day keep the API's order.
+        releases.sort_by(|a, b| b.date.cmp(&a.date));
+        ReleaseIndex { releases }
+    }
+
+    /// Names of all channels with at least one build, sorted.
+    pub fn channels(&self) -> Vec<String> {
+        let mut channels: Vec<String> = self
+            .releases
+            .iter()
+            .map(|release| release.channel.clone())
+            .collect();
+        channels.sort();
+        channels.dedup();
+        channels
+    }
+
+    /// Whether the builds read so far settle which one `spec` selects.  The
+    /// listing runs newest first, so later pages can only hold older builds.
+    fn settles(&self, spec: &ChannelSpec) -> bool {
+        let mut builds = self
+            .releases
+            .iter()
+            .filter(|release| release.channel == spec.channel);
+        match &spec.pin {
+            None => builds.next().is_some(),
+            Some(Pin::Date(date)) => self.releases.iter().any(|release| {
+                release.date < *date || (release.channel == spec.channel && release.date == *date)
+            }),
+            Some(Pin::Commit(prefix)) => {
+                builds.any(|release| release.commit.starts_with(prefix.as_str()))
+            }
+        }
+    }
+
+    /// The latest build of `channel`.
+    pub fn latest(&self, channel: &str) -> Option<&Release> {
+        self.releases
+            .iter()
+            .find(|release| release.channel == channel)
+    }
+
+    /// The build that `spec` selects.
+    pub fn resolve(&self, spec: &ChannelSpec) -> Result<&Release> {
+        let mut builds = self
+            .releases
+            .iter()
+            .filter(|release| release.channel == spec.channel)
+            .peekable();
+        if builds.peek().is_none() {
+            return Err(anyhow::anyhow!(
+                "No builds of channel '{}' found in the release index (available: {})",
+                spec.channel,
+                self.channels().join(", ")
+            ));
+        }
+
+        match &spec.pin {
+            None => Ok(builds.next().unwrap()),
+            Some(Pin::Date(date)) => {
+                builds.find(|release| &release.date == date).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No build of channel '{}' was published on {}",
+                        spec.channel,
+                        date
+                    )
+                })
+            }
+            Some(Pin::Commit(prefix)) => {
+                let matches: Vec<&Release> = builds
+                    .filter(|release| release.commit.starts_with(prefix.as_str()))
+                    .collect();
+                match matches.as_slice() {
+                    [release] => Ok(release),
+                    [] => Err(anyhow::anyhow!(
+                        "No build of channel '{}' matches commit {}",
+                        spec.channel,
+                        prefix
+                    )),
+                    _ => Err(anyhow::anyhow!(
+                        "Commit {} is ambiguous in channel '{}'; use more characters",
+                        prefix,
+                        spec.channel
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Resolve `spec` to a commit, consulting `index` only when the spec does not
+/// already name a full commit.
+pub async fn resolve_commit(spec: &ChannelSpec, index: Option<&ReleaseIndex>) -> Result<String> {
+    if let Some(commit) = spec.full_commit() {
+        return Ok(commit.to_string());
+    }
+    match index {
+        Some(index) => Ok(index.resolve(spec)?.commit.clone()),
+        None => Ok(ReleaseIndex::fetch_for(std::slice::from_ref(spec))
+            .await?
+            .resolve(spec)?
+            .commit
+            .clone()),
+    }
+}
@@ -1,4 +1,9 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, ArgValueCompleter};
+
+use crate::commands::completions;
 
 #[derive(Parser)]
 #[command(name = "nockup")]
@@ -12,22 +17,47 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Initialize nockup cache and download templates
-    Install,
+    Install {
+        /// Install from a local bundle directory instead of the network
+        #[arg(long, value_name = "DIR")]
+        from: Option<PathBuf>,
+        /// Installation profile listing the components to install (e.g., default, minimal)
+        #[arg(long)]
+        profile: Option<String>,
+        /// Pin project templates to a tag or commit instead of following the templates branch
+        #[arg(long, value_name = "REF")]
+        templates_ref: Option<String>,
+        /// Do not add nockup to PATH in shell startup files
+        #[arg(long)]
+        no_modify_path: bool,
+    },
     /// Initialize a new NockApp project from a .toml config file
     Init {
         /// Name of the project config file (looks for <name>.toml)
+        #[arg(add = ArgValueCandidates::new(completions::project_configs))]
         name: String,
     },
     /// Check for updates to nockup, hoon, and hoonc
-    Update,
+    Update {
+        /// Update from a local bundle directory instead of the network
+        #[arg(long, value_name = "DIR")]
+        from: Option<PathBuf>,
+        /// Report available updates without installing them (exits with 100 if there are any)
+        #[arg(long)]
+        check: bool,
+    },
+    /// Restore the nockup binary and default toolchain from before the last update
+    Rollback,
     /// Build a NockApp project
     Build {
         /// Path to the project directory
+        #[arg(add = ArgValueCompleter::new(completions::project_dirs))]
         project: String,
     },
     /// Run a NockApp project
     Run {
         /// Path to the project directory  
+        #[arg(add = ArgValueCompleter::new(completions::project_dirs))]
         project: String,
         /// Additional arguments to pass to the running application
         #[arg(last = true)]
@@ -38,14 +68,212 @@ pub enum Commands {
         #[command(subcommand)]
         action: ChannelAction,
     },
+    /// Manage installed hoon/hoonc toolchains
+    Toolchain {
+        #[command(subcommand)]
+        action: ToolchainAction,
+    },
+    /// Download the default toolchain for other machines' target triples
+    Target {
+        #[command(subcommand)]
+        action: TargetAction,
+    },
+    /// View and change nockup settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Update or uninstall nockup itself
+    #[command(name = "self")]
+    SelfCmd {
+        #[command(subcommand)]
+        action: SelfAction,
+    },
+    /// Create and manage offline install bundles
+    Bundle {
+        #[command(subcommand)]
+        action: BundleAction,
+    },
+    /// Check the nockup installation and environment for problems
+    Doctor {
+        /// Repair the problems that can be fixed safely
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Re-check installed binaries against their install receipts and channel manifests
+    Verify,
+    /// Show everything nockup has installed, with the disk space each item uses
+    Show {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a script that enables shell completions for nockup
+    Completions {
+        /// Shell to generate completions for
+        shell: CompletionShell,
+    },
+    /// Run a command with a toolchain's hoon and hoonc first on PATH
+    Exec {
+        /// Command to run, optionally preceded by +toolchain (e.g., +nightly)
+        #[arg(
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "COMMAND"
+        )]
+        command: Vec<std::ffi::OsString>,
+    },
+}
+
+impl Commands {
+    /// Whether the command changes anything under `~/.nockup`, and so must
+    /// hold the lock on it.  Commands that only install a toolchain on demand
+    /// take the lock when they do.
+    pub fn mutates_home(&self) -> bool {
+        match self {
+            Commands::Install { .. }
+            | Commands::Init { .. }
+            | Commands::Update { check: false, .. }
+            | Commands::Rollback
+            | Commands::SelfCmd { .. }
+            | Commands::Bundle { .. } => true,
+            Commands::Channel { action } => matches!(action, ChannelAction::Set { .. }),
+            Commands::Toolchain { action } => !matches!(action, ToolchainAction::List),
+            Commands::Target { action } => !matches!(action, TargetAction::List),
+            Commands::Config { action } => {
+                matches!(
+                    action,
+                    ConfigAction::Set { .. } | ConfigAction::Unset { .. }
+                )
+            }
+            Commands::Doctor { fix } => *fix,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Powershell,
 }
 
 #[derive(Subcommand)]
 pub enum ChannelAction {
-    /// Set the default channel (e.g., stable, nightly)
+    /// Set the default channel (e.g., stable, nightly, stable-2025-09-01, stable@<commit>)
     Set {
+        #[arg(add = ArgValueCandidates::new(completions::channels))]
         channel: String, // e.g., "stable" or "nightly"
     },
     /// Show the current channel
     Show,
+    /// List channels published in the release index
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum ToolchainAction {
+    /// List installed toolchains
+    List,
+    /// Install a toolchain alongside any existing ones
+    Install {
+        /// Channel to install from (e.g., stable, nightly, stable-2025-09-01, stable@<commit>)
+        #[arg(add = ArgValueCandidates::new(completions::channels))]
+        channel: String,
+        /// Exact nockchain commit to install instead of the channel's latest build
+        #[arg(long)]
+        commit: Option<String>,
+    },
+    /// Remove an installed toolchain
+    Uninstall {
+        /// Toolchain name as shown by `nockup toolchain list`
+        #[arg(add = ArgValueCandidates::new(completions::toolchains))]
+        toolchain: String,
+    },
+    /// Set the default toolchain
+    Default {
+        /// Toolchain name as shown by `nockup toolchain list`
+        #[arg(add = ArgValueCandidates::new(completions::toolchains))]
+        toolchain: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TargetAction {
+    /// Download and verify hoon and hoonc for additional target triples
+    Add {
+        /// Target triples, e.g. aarch64-apple-darwin
+        #[arg(required = true, value_name = "TARGET")]
+        targets: Vec<String>,
+    },
+    /// Remove the binaries downloaded for target triples
+    Remove {
+        /// Target triples as shown by `nockup target list`
+        #[arg(
+            required = true,
+            value_name = "TARGET",
+            add = ArgValueCandidates::new(completions::targets)
+        )]
+        targets: Vec<String>,
+    },
+    /// List the targets the default toolchain is built for
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print the effective value of a setting
+    Get {
+        /// Setting key, e.g. channel or mirrors.api
+        #[arg(add = ArgValueCandidates::new(completions::config_keys))]
+        key: String,
+    },
+    /// Change a setting in config.toml (signing.keys takes several values)
+    Set {
+        /// Setting key, e.g. channel or mirrors.api
+        #[arg(add = ArgValueCandidates::new(completions::config_keys))]
+        key: String,
+        /// New value
+        #[arg(required = true, value_name = "VALUE")]
+        values: Vec<String>,
+    },
+    /// Remove a setting from config.toml, restoring its default
+    Unset {
+        /// Setting key, e.g. channel or mirrors.api
+        #[arg(add = ArgValueCandidates::new(completions::config_keys))]
+        key: String,
+    },
+    /// List every setting with its value and where that value comes from
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum BundleAction {
+    /// Download everything needed for an offline install into a directory
+    Create {
+        /// Directory to write the bundle to
+        dir: PathBuf,
+        /// Channels to include, e.g. stable or stable-2025-09-01 (defaults to every published channel)
+        #[arg(long = "channel", value_name = "CHANNEL")]
+        channels: Vec<String>,
+        /// Target triples to include (defaults to this machine's)
+        #[arg(long = "target", value_name = "TARGET")]
+        targets: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SelfAction {
+    /// Replace this nockup executable with the latest build from the current channel
+    Update,
+    /// Remove nockup, its cache, and the PATH entries it added to shell startup files
+    Uninstall {
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
 }
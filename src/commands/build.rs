@@ -5,6 +5,8 @@ use anyhow::{Context, Result};
 use colored::Colorize;
 use tokio::process::Command;
 
+use crate::toolchain;
+
 pub async fn run(project: String) -> Result<()> {
     let project_dir = Path::new(&project);
 
@@ -17,7 +19,8 @@ pub async fn run(project: String) -> Result<()> {
     let manifest_path = project_dir.join("manifest.toml");
     if !manifest_path.exists() {
         return Err(anyhow::anyhow!(
-            "Not a NockApp project: '{}' missing manifest.toml", project
+            "Not a NockApp project: '{}' missing manifest.toml",
+            project
         ));
     }
 
@@ -27,6 +30,9 @@ pub async fn run(project: String) -> Result<()> {
         return Err(anyhow::anyhow!("No Cargo.toml found in '{}'", project));
     }
 
+    // Resolve the toolchain pinned by the project, if any
+    let toolchain_bin = toolchain::resolve_project_toolchain(project_dir).await?;
+
     println!("{} Building project '{}'...", "🔨".green(), project.cyan());
 
     // Extract expected binary names from Cargo.toml
@@ -50,9 +56,7 @@ pub async fn run(project: String) -> Result<()> {
 
     // Check number of expected binaries; if more than one, check primary source files.
     let binaries: Vec<std::path::PathBuf> = {
-        if expected_binaries.is_empty() {
-            vec![project_dir.join("src").join("main.rs")]
-        } else if expected_binaries.len() == 1 {
+        if expected_binaries.len() <= 1 {
             vec![project_dir.join("src").join("main.rs")]
         } else {
             let mut binaries = Vec::new();
@@ -72,6 +76,9 @@ pub async fn run(project: String) -> Result<()> {
         .current_dir(project_dir)
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit());
+    if let Some(bin_dir) = &toolchain_bin {
+        toolchain::prepend_to_path(&mut cargo_command, bin_dir)?;
+    }
 
     let status = cargo_command
         .status()
@@ -110,7 +117,11 @@ pub async fn run(project: String) -> Result<()> {
         println!("{} Compiling Hoon app...", "📦".green());
 
         // Run hoonc command from project directory
-        let mut hoonc_command = Command::new("hoonc");
+        let hoonc = match &toolchain_bin {
+            Some(bin_dir) => bin_dir.join("hoonc"),
+            None => "hoonc".into(),
+        };
+        let mut hoonc_command = Command::new(hoonc);
         hoonc_command
             .arg(hoon_app_path.strip_prefix(project_dir).unwrap())
             .current_dir(project_dir) // Run in project directory
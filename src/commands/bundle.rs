@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use super::common::{self, BundleFile, BundleInfo, Source, BUNDLE_FILE};
+use crate::channel::{ChannelSpec, ReleaseIndex};
+use crate::cli::BundleAction;
+use crate::config::NockupConfig;
+
+pub async fn run(action: BundleAction) -> Result<()> {
+    match action {
+        BundleAction::Create {
+            dir,
+            channels,
+            targets,
+        } => create(&dir, channels, targets).await,
+    }
+}
+
+/// Populate `bundle_dir` with the channel manifests, release archives,
+/// signatures and templates that `nockup install --from` needs.
+async fn create(bundle_dir: &Path, channels: Vec<String>, targets: Vec<String>) -> Result<()> {
+    let specs = channels
+        .iter()
+        .map(|channel| ChannelSpec::parse(channel))
+        .collect::<Result<Vec<_>>>()?;
+    let targets = if targets.is_empty() {
+        vec![common::get_target_identifier()]
+    } else {
+        targets
+    };
+
+    if bundle_dir.exists() && fs::read_dir(bundle_dir)?.next().is_some() {
+        return Err(anyhow::anyhow!(
+            "Bundle directory {} is not empty",
+            bundle_dir.display()
+        ));
+    }
+    fs::create_dir_all(bundle_dir)
+        .with_context(|| format!("Failed to create {}", bundle_dir.display()))?;
+
+    println!(
+        "{} Creating bundle in {}",
+        "📦".green(),
+        bundle_dir.display().to_string().cyan()
+    );
+
+    // The bundle mirrors the cache layout for templates and channel manifests,
+    // with the templates at the configured ref if one is pinned.
+    let templates_ref = NockupConfig::load()
+        .ok()
+        .and_then(|config| config.templates_ref);
+    common::download_templates(bundle_dir, &Source::Remote, templates_ref.as_deref()).await?;
+    let (index, specs) = if specs.is_empty() {
+        let index = ReleaseIndex::fetch().await?;
+        let specs = index
+            .channels()
+            .into_iter()
+            .map(|channel| ChannelSpec { channel, pin: None })
+            .collect();
+        (index, specs)
+    } else {
+        (ReleaseIndex::fetch_for(&specs).await?, specs)
+    };
+    let toolchains_dir = bundle_dir.join("toolchains");
+    common::write_channel_manifests(&toolchains_dir, &specs, &index).await?;
+
+    let mut commit = None;
+    for spec in &specs {
+        let manifest_path = toolchains_dir.join(format!("channel-nockup-{}.toml", spec));
+        let manifest = common::read_channel_manifest(&manifest_path)
+            .with_context(|| format!("No manifest available for channel '{}'", spec))?;
+        if commit.is_none() {
+            commit = manifest.commit.clone();
+        }
+
+        // Every package is bundled so that any profile can be installed.
+        let packages: Vec<&str> = manifest.pkg.keys().map(String::as_str).collect();
+        for target in &targets {
+            common::bundle_components(&manifest, target, &packages, bundle_dir).await?;
+        }
+    }
+
+    let commit = match commit {
+        Some(commit) => commit,
+        None => Source::Remote.commit_id().await?,
+    };
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is before the Unix epoch")?
+        .as_secs();
+    let bundle = BundleFile {
+        bundle: BundleInfo {
+            created,
+            commit,
+            channels: specs.iter().map(ChannelSpec::to_string).collect(),
+            targets,
+        },
+    };
+    fs::write(bundle_dir.join(BUNDLE_FILE), toml::to_string(&bundle)?)
+        .context("Failed to write bundle file")?;
+
+    println!("{} Bundle created!", "✅".green());
+    println!(
+        "Install it offline with: {}",
+        format!("nockup install --from {}", bundle_dir.display()).cyan()
+    );
+    Ok(())
+}
@@ -1,48 +1,115 @@
-use std::path::PathBuf;
-
 use anyhow::{Context, Result};
 
+use crate::channel::{self, ChannelSpec, ReleaseIndex};
 use crate::cli::ChannelAction;
+use crate::commands::common;
+use crate::config::{self, NockupConfig};
 
-fn set_channel(channel: &str) -> Result<()> {
-    // validate that is 'nightly' or 'stable', change later when more are supported
-    if channel != "nightly" && channel != "stable" {
-        return Err(anyhow::anyhow!("Invalid channel: {}", channel));
-    }
-    let mut config = get_config()?;
-    config["channel"] = toml::Value::String(channel.to_string());
-    let cache_dir = get_cache_dir()?;
-    let config_path = cache_dir.join("config.toml");
-    std::fs::write(config_path, toml::to_string(&config)?)
-        .context("Failed to write config file")?;
-    println!("Set default channel to '{}'.", channel);
+/// Make `channel` the default channel once its build is found in the release
+/// index, caching the build's manifest for the next `nockup update`.
+async fn set_channel(channel: &str) -> Result<()> {
+    let spec = ChannelSpec::parse(channel)?;
+    let mut config = NockupConfig::load()?;
+    let cache_dir = config::nockup_home()?;
+
+    let commit = channel::resolve_commit(&spec, None).await?;
+    let content = common::fetch_channel_manifest(&spec.channel, &commit).await?;
+    let toolchains_dir = cache_dir.join("toolchains");
+    std::fs::create_dir_all(&toolchains_dir)?;
+    std::fs::write(
+        toolchains_dir.join(format!("channel-nockup-{}.toml", spec)),
+        content,
+    )
+    .context("Failed to write manifest file")?;
+
+    config.channel = spec.to_string();
+    config.save()?;
+    println!(
+        "Set default channel to '{}' (build {}).  Run 'nockup update' to install it.",
+        spec,
+        &commit[..commit.len().min(7)]
+    );
     Ok(())
 }
 
 fn show_channel() -> Result<()> {
-    let config = get_config()?;
-    println!("Default channel: {}", config["channel"]);
-    println!("Architecture: {}", config["architecture"]);
+    let config = NockupConfig::load()?;
+    println!("Default channel: {}", config.channel);
+    println!("Architecture: {}", config.architecture);
     Ok(())
 }
 
-fn get_cache_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-    Ok(home.join(".nockup"))
+/// List the channels published in the release index, or those cached
+/// locally if the index cannot be reached.
+async fn list_channels() -> Result<()> {
+    let current = NockupConfig::load().ok().map(|config| config.channel);
+    let default_marker = |channel: &str| match current.as_deref() {
+        Some(current) if current == channel => " (default)".to_string(),
+        Some(current) if ChannelSpec::parse(current).is_ok_and(|spec| spec.channel == channel) => {
+            format!(" (default: {})", current)
+        }
+        _ => String::new(),
+    };
+
+    let index = match ReleaseIndex::fetch().await {
+        Ok(index) => index,
+        Err(e) => {
+            println!(
+                "Could not fetch the release index ({}); showing cached channels.",
+                e
+            );
+            return list_cached_channels(default_marker);
+        }
+    };
+
+    let channels = index.channels();
+    if channels.is_empty() {
+        println!("No channels published in the release index.");
+        return Ok(());
+    }
+    for channel in channels {
+        let latest = index.latest(&channel).expect("listed channels have builds");
+        println!(
+            "{} (latest build {} on {}){}",
+            channel,
+            &latest.commit[..latest.commit.len().min(7)],
+            latest.date,
+            default_marker(&channel)
+        );
+    }
+    Ok(())
 }
 
-fn get_config() -> Result<toml::Value> {
-    let cache_dir = get_cache_dir()?;
-    let config_path = cache_dir.join("config.toml");
-    let config_str = std::fs::read_to_string(&config_path).context("Failed to read config file")?;
-    let config: toml::Value =
-        toml::de::from_str(&config_str).context("Failed to parse config file")?;
-    Ok(config)
+fn list_cached_channels(default_marker: impl Fn(&str) -> String) -> Result<()> {
+    let toolchains_dir = config::nockup_home()?.join("toolchains");
+    let mut channels = Vec::new();
+    if toolchains_dir.exists() {
+        for entry in std::fs::read_dir(&toolchains_dir)? {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(channel) = file_name
+                .strip_prefix("channel-nockup-")
+                .and_then(|name| name.strip_suffix(".toml"))
+            {
+                channels.push(channel.to_string());
+            }
+        }
+    }
+    channels.sort();
+
+    if channels.is_empty() {
+        println!("No channel manifests found. Run 'nockup install' first.");
+        return Ok(());
+    }
+    for channel in channels {
+        println!("{}{}", channel, default_marker(&channel));
+    }
+    Ok(())
 }
 
 pub async fn run(command: ChannelAction) -> Result<()> {
     match command {
-        ChannelAction::Set { channel } => set_channel(&channel),
+        ChannelAction::Set { channel } => set_channel(&channel).await,
         ChannelAction::Show => show_channel(),
+        ChannelAction::List => list_channels().await,
     }
 }
@@ -1,23 +1,214 @@
 use std::fs;
 use std::io::Read;
-use std::path::PathBuf;
-use std::process::Stdio;
+use std::path::{Path, PathBuf};
 
-use anyhow::{anyhow, Context, Result};
-use blake3;
+use anyhow::{Context, Result};
 use colored::Colorize;
 use flate2::read::GzDecoder;
-use sha1::{Digest, Sha1};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
 use tar::Archive;
 use tokio::fs as tokio_fs;
-use tokio::process::Command;
 
-const GITHUB_REPO: &str = "sigilante/nockup";
-const TEMPLATES_BRANCH: &str = "master";
+use crate::channel::{self, ChannelSpec, ReleaseIndex};
+use crate::config::{self, NockupConfig};
+use crate::download::{download_file, hash_file, verify_checksums, Artifact, CONCURRENT_DOWNLOADS};
+use crate::generation;
+use crate::github;
+use crate::manifest::{ChannelManifest, Target};
+use crate::pgp;
+use crate::receipt::{self, ComponentReceipt, Receipt};
+use crate::status::Status;
+use crate::templates;
+use crate::toolchain;
+
+pub const DEFAULT_API_URL: &str = "https://api.github.com/repos/nockchain/nockchain";
+pub const DEFAULT_RELEASES_URL: &str = "https://github.com/nockchain/nockchain/releases/download";
+pub const DEFAULT_TEMPLATES_URL: &str = "https://github.com/sigilante/nockup.git";
+pub const DEFAULT_TEMPLATES_BRANCH: &str = "master";
+
+/// Remote endpoints nockup talks to.  Each one can be overridden in the
+/// `[mirrors]` table of `config.toml` or with an environment variable, the
+/// latter taking precedence.
+#[derive(Debug, Clone)]
+pub struct Mirrors {
+    /// GitHub API base for the nockchain repository (`NOCKUP_API_URL`, `api`).
+    pub api: String,
+    /// Base URL that release assets are downloaded from (`NOCKUP_RELEASES_URL`,
+    /// `releases`).
+    pub releases: String,
+    /// Git URL of the templates repository (`NOCKUP_TEMPLATES_URL`, `templates`).
+    pub templates: String,
+    /// Branch of the templates repository (`NOCKUP_TEMPLATES_BRANCH`,
+    /// `templates_branch`).
+    pub templates_branch: String,
+    /// GitHub API base for the templates repository
+    /// (`NOCKUP_TEMPLATES_API_URL`, `templates_api`).  Derived from
+    /// `templates` for GitHub repositories; `None` if templates can only be
+    /// fetched with git.
+    pub templates_api: Option<String>,
+    /// Base URL serving `<commit>.tar.gz` source archives of the templates
+    /// repository (`NOCKUP_TEMPLATES_ARCHIVE_URL`, `templates_archive`).
+    pub templates_archive: Option<String>,
+}
+
+impl Mirrors {
+    pub fn load() -> Self {
+        let mirrors = NockupConfig::load()
+            .map(|config| config.mirrors)
+            .unwrap_or_default();
+        let optional_setting = |env: &str, configured: &Option<String>| {
+            std::env::var(env)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .or_else(|| configured.clone())
+                .map(|value| value.trim_end_matches('/').to_string())
+        };
+        let setting = |env: &str, configured: &Option<String>, default: &str| {
+            optional_setting(env, configured).unwrap_or_else(|| default.to_string())
+        };
+        let templates = setting(
+            "NOCKUP_TEMPLATES_URL",
+            &mirrors.templates,
+            DEFAULT_TEMPLATES_URL,
+        );
+        let github_repo = templates
+            .strip_prefix("https://github.com/")
+            .map(|repo| repo.trim_end_matches(".git").to_string())
+            .filter(|repo| repo.split('/').count() == 2);
+        Mirrors {
+            api: setting("NOCKUP_API_URL", &mirrors.api, DEFAULT_API_URL),
+            releases: setting(
+                "NOCKUP_RELEASES_URL",
+                &mirrors.releases,
+                DEFAULT_RELEASES_URL,
+            ),
+            templates_branch: setting(
+                "NOCKUP_TEMPLATES_BRANCH",
+                &mirrors.templates_branch,
+                DEFAULT_TEMPLATES_BRANCH,
+            ),
+            templates_api: optional_setting("NOCKUP_TEMPLATES_API_URL", &mirrors.templates_api)
+                .or_else(|| {
+                    github_repo
+                        .as_ref()
+                        .map(|repo| format!("https://api.github.com/repos/{}", repo))
+                }),
+            templates_archive: optional_setting(
+                "NOCKUP_TEMPLATES_ARCHIVE_URL",
+                &mirrors.templates_archive,
+            )
+            .or_else(|| {
+                github_repo
+                    .as_ref()
+                    .map(|repo| format!("https://github.com/{}/archive", repo))
+            }),
+            templates,
+        }
+    }
+
+    /// Map a release asset URL from a channel manifest onto the configured
+    /// releases mirror.  URLs outside the upstream release pages are only
+    /// upgraded to HTTPS, and URLs already on the mirror are left untouched.
+    pub fn artifact_url(&self, url: &str) -> String {
+        if url.starts_with(&self.releases) {
+            return url.to_string();
+        }
+        let url = url.replace("http://", "https://");
+        match url.strip_prefix(DEFAULT_RELEASES_URL) {
+            Some(rest) => format!("{}{}", self.releases, rest),
+            None => url,
+        }
+    }
+}
+
+/// Name of the metadata file at the root of an offline bundle.
+pub const BUNDLE_FILE: &str = "bundle.toml";
+
+/// Where channel manifests, release archives and templates are fetched from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// GitHub and the nockchain release pages.
+    Remote,
+    /// A local bundle directory produced by `nockup bundle create`.
+    Bundle(PathBuf),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BundleFile {
+    pub bundle: BundleInfo,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BundleInfo {
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    pub commit: String,
+    pub channels: Vec<String>,
+    pub targets: Vec<String>,
+}
+
+impl Source {
+    pub fn from_option(bundle_dir: Option<PathBuf>) -> Result<Self> {
+        match bundle_dir {
+            None => Ok(Source::Remote),
+            Some(bundle_dir) => {
+                read_bundle_info(&bundle_dir)?;
+                Ok(Source::Bundle(bundle_dir))
+            }
+        }
+    }
+
+    /// The nockchain commit the installation tracks.
+    pub async fn commit_id(&self) -> Result<String> {
+        match self {
+            Source::Remote => get_git_commit_id().await,
+            Source::Bundle(bundle_dir) => Ok(read_bundle_info(bundle_dir)?.commit),
+        }
+    }
+
+    /// Make the release artifact at `url` available locally.
+    async fn fetch_artifact(&self, url: &str) -> Result<Artifact> {
+        match self {
+            Source::Remote => download_file(url).await,
+            Source::Bundle(bundle_dir) => {
+                let path = bundle_artifact_path(bundle_dir, url);
+                if !path.exists() {
+                    return Err(anyhow::anyhow!(
+                        "Bundle at {} does not contain {}",
+                        bundle_dir.display(),
+                        artifact_file_name(url)
+                    ));
+                }
+                Artifact::local(path)
+            }
+        }
+    }
+}
+
+pub fn read_bundle_info(bundle_dir: &Path) -> Result<BundleInfo> {
+    let bundle_path = bundle_dir.join(BUNDLE_FILE);
+    if !bundle_path.exists() {
+        return Err(anyhow::anyhow!(
+            "Not a nockup bundle: {} is missing {}",
+            bundle_dir.display(),
+            BUNDLE_FILE
+        ));
+    }
+    let content = fs::read_to_string(&bundle_path)
+        .with_context(|| format!("Failed to read {}", bundle_path.display()))?;
+    let file: BundleFile = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", bundle_path.display()))?;
+    Ok(file.bundle)
+}
+
+pub fn artifact_file_name(url: &str) -> &str {
+    url.split('/').next_back().unwrap_or("download")
+}
 
-pub fn get_cache_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-    Ok(home.join(".nockup"))
+/// Location of a release artifact inside a bundle, keyed by its file name.
+pub fn bundle_artifact_path(bundle_dir: &Path, url: &str) -> PathBuf {
+    bundle_dir.join("releases").join(artifact_file_name(url))
 }
 
 pub fn get_target_identifier() -> String {
@@ -35,52 +226,114 @@ pub fn get_target_identifier() -> String {
     }
 }
 
-pub fn get_config() -> Result<toml::Value> {
-    let cache_dir = get_cache_dir()?;
-    let config_path = cache_dir.join("config.toml");
-    if !config_path.exists() {
-        return Err(anyhow::anyhow!(
-            "Config file not found. Please run 'nockup install' first."
-        ));
+/// Directory holding `templates/` and `manifests/` for `templates_ref`: the
+/// cache directory itself when following the templates branch, or one
+/// directory per pinned ref so several versions stay cached side by side.
+pub fn templates_root(cache_dir: &Path, templates_ref: Option<&str>) -> Result<PathBuf> {
+    match templates_ref {
+        None => Ok(cache_dir.to_path_buf()),
+        Some(git_ref) => {
+            config::validate_git_ref(git_ref).context("Invalid templates_ref in config.toml")?;
+            Ok(cache_dir
+                .join("template-refs")
+                .join(git_ref.replace('/', "-")))
+        }
     }
-    let config_str = std::fs::read_to_string(&config_path).context("Failed to read config file")?;
-    let config: toml::Value =
-        toml::de::from_str(&config_str).context("Failed to parse config file")?;
-    Ok(config)
 }
 
-pub fn get_or_create_config() -> Result<toml::Value> {
-    let cache_dir = get_cache_dir()?;
-    let config_path = cache_dir.join("config.toml");
-    if !config_path.exists() {
-        write_default_config(&config_path)?;
+/// The templates `nockup init` uses:  those of the configured
+/// `templates_ref`, or of the templates branch.
+pub fn templates_dir() -> Result<PathBuf> {
+    let templates_ref = NockupConfig::load()
+        .ok()
+        .and_then(|config| config.templates_ref);
+    Ok(templates_root(&config::nockup_home()?, templates_ref.as_deref())?.join("templates"))
+}
+
+/// The ref and commit of the templates repository that a templates directory
+/// was downloaded from.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TemplatesVersion {
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub commit: String,
+}
+
+/// `commit.toml` in a templates directory.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CommitFile {
+    /// The nockchain commit the templates branch was last checked against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commit: Option<CommitId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template: Option<TemplatesVersion>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CommitId {
+    id: String,
+}
+
+fn read_commit_file(templates_dir: &Path) -> Result<Option<CommitFile>> {
+    let commit_file = templates_dir.join("commit.toml");
+    match fs::read_to_string(&commit_file) {
+        Ok(content) => Ok(Some(
+            toml::from_str(&content).context("Failed to parse commit file")?,
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Failed to read commit file: {}", e)),
     }
-    let config_str = std::fs::read_to_string(&config_path).context("Failed to read config file")?;
-    let config: toml::Value =
-        toml::de::from_str(&config_str).context("Failed to parse config file")?;
-    Ok(config)
 }
 
-fn write_default_config(config_path: &PathBuf) -> Result<()> {
-    let default_config = format!(
-        r#"channel = "stable"
-architecture = "{}"
-"#,
-        get_target_identifier()
-    );
-    std::fs::write(config_path, default_config).context("Failed to create default config file")?;
-    Ok(())
+/// The version of the templates in `templates_dir`, if it was recorded.
+pub fn templates_version(templates_dir: &Path) -> Option<TemplatesVersion> {
+    read_commit_file(templates_dir).ok()??.template
 }
 
-pub async fn download_templates(cache_dir: &PathBuf) -> Result<()> {
-    let templates_dir = cache_dir.join("templates");
+/// Download the project templates and manifests into `root` (see
+/// `templates_root`), at `templates_ref` if given or else from the templates
+/// branch.
+pub async fn download_templates(
+    root: &Path,
+    source: &Source,
+    templates_ref: Option<&str>,
+) -> Result<()> {
+    let templates_dir = root.join("templates");
+
+    if let Source::Bundle(bundle_dir) = source {
+        println!("{} Copying templates from bundle...", "📦".green());
+        for dir_name in ["templates", "manifests"] {
+            let src = bundle_dir.join(dir_name);
+            let dst = root.join(dir_name);
+            if !src.exists() {
+                return Err(anyhow::anyhow!(
+                    "Bundle at {} has no '{}' directory",
+                    bundle_dir.display(),
+                    dir_name
+                ));
+            }
+            if dst.exists() {
+                fs::remove_dir_all(&dst)
+                    .with_context(|| format!("Failed to remove {}", dst.display()))?;
+            }
+            copy_dir_recursive(&src, &dst)?;
+        }
+        println!(
+            "{} Templates and manifests copied successfully",
+            "✓".green()
+        );
+        return Ok(());
+    }
 
+    if let Some(git_ref) = templates_ref {
+        return fetch_pinned_templates(root, git_ref).await;
+    }
     if has_existing_templates(&templates_dir).await? {
         println!("{} Existing templates found, updating...", "🔄".yellow());
-        update_templates(&templates_dir).await?;
+        update_templates(root).await?;
     } else {
         println!("{}  Downloading templates from GitHub...", "⬇️".green());
-        clone_templates(&templates_dir).await?;
+        clone_templates(root).await?;
     }
 
     Ok(())
@@ -112,114 +365,130 @@ async fn has_existing_templates(templates_dir: &PathBuf) -> Result<bool> {
     Ok(false)
 }
 
-async fn clone_templates(templates_dir: &PathBuf) -> Result<()> {
-    let commit_id = get_git_commit_id().await?;
-    let commit_file = templates_dir.join("commit.toml");
-
-    match tokio_fs::read_to_string(&commit_file).await {
-        Ok(commit_content) => {
-            let commit: toml::Value =
-                toml::de::from_str(&commit_content).context("Failed to parse commit file")?;
-            let local_commit_id = commit["commit"]["id"].to_string().replace("\"", "");
-            if local_commit_id == commit_id {
-                println!("{} Templates are up to date", "✅".green());
-                return Ok(());
-            }
-        }
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            println!("{} No local commit ID found", "🔍".yellow());
-        }
-        Err(e) => {
-            return Err(anyhow::anyhow!("Failed to read commit file: {}", e));
+/// Download the templates at a pinned ref, unless the commit it names is
+/// already cached.  The ref is looked up each time, so a pinned branch picks
+/// up new commits.
+async fn fetch_pinned_templates(root: &Path, git_ref: &str) -> Result<()> {
+    let templates_dir = root.join("templates");
+    if let Some(version) = templates_version(&templates_dir) {
+        if version.git_ref == git_ref && version.commit == templates::resolve(git_ref).await? {
+            println!(
+                "{} Templates {} ({}) are already downloaded",
+                "✅".green(),
+                git_ref.cyan(),
+                &version.commit[..version.commit.len().min(7)]
+            );
+            return Ok(());
         }
     }
 
-    if templates_dir.exists() {
-        fs::remove_dir_all(templates_dir)
-            .context("Failed to remove existing templates directory")?;
+    println!(
+        "{}  Downloading templates {}...",
+        "⬇️".green(),
+        git_ref.cyan()
+    );
+    let commit = templates::fetch(root, git_ref).await?;
+    let commit_file = CommitFile {
+        commit: None,
+        template: Some(TemplatesVersion {
+            git_ref: git_ref.to_string(),
+            commit: commit.clone(),
+        }),
+    };
+    fs::write(
+        templates_dir.join("commit.toml"),
+        toml::to_string(&commit_file)?,
+    )?;
+    println!(
+        "{} Templates {} ({}) downloaded successfully",
+        "✓".green(),
+        git_ref,
+        &commit[..commit.len().min(7)]
+    );
+    Ok(())
+}
 
-        if templates_dir.exists() {
-            return Err(anyhow::anyhow!(
-                "Failed to completely remove templates directory at {}",
-                templates_dir.display()
-            ));
+/// The templates in `root` that downloading them from `source` would replace,
+/// as their installed and latest versions, or `None` if they are current.
+pub async fn pending_templates(
+    root: &Path,
+    source: &Source,
+    templates_ref: Option<&str>,
+) -> Result<Option<(String, String)>> {
+    let templates_dir = root.join("templates");
+    let label = |version: &TemplatesVersion| {
+        format!(
+            "{} ({})",
+            version.git_ref,
+            &version.commit[..version.commit.len().min(7)]
+        )
+    };
+    let installed = templates_version(&templates_dir);
+    let old = installed
+        .as_ref()
+        .map(label)
+        .unwrap_or_else(|| "(not installed)".to_string());
+
+    let latest = match (source, templates_ref) {
+        (Source::Bundle(bundle_dir), _) => match templates_version(&bundle_dir.join("templates")) {
+            Some(latest) => latest,
+            None => return Ok(Some((old, "(bundled)".to_string()))),
+        },
+        (Source::Remote, Some(git_ref)) => TemplatesVersion {
+            git_ref: git_ref.to_string(),
+            commit: templates::resolve(git_ref).await?,
+        },
+        (Source::Remote, None) => {
+            // The templates branch is checked the way `clone_templates` does.
+            let latest = get_git_commit_id().await?;
+            let local = read_commit_file(&templates_dir)?.and_then(|file| file.commit);
+            if local.is_some_and(|local| local.id == latest) {
+                return Ok(None);
+            }
+            let branch = Mirrors::load().templates_branch;
+            return Ok(Some((old, format!("{} (latest)", branch))));
         }
-    }
-
-    let temp_dir = templates_dir.parent().unwrap().join("temp_repo");
-    if temp_dir.exists() {
-        fs::remove_dir_all(&temp_dir)?;
-    }
-
-    let repo_url = format!("https://github.com/{}.git", GITHUB_REPO);
-
-    let mut command = Command::new("git");
-    command
-        .arg("clone")
-        .arg("--depth=1")
-        .arg("--branch")
-        .arg(TEMPLATES_BRANCH)
-        .arg(&repo_url)
-        .arg(&temp_dir);
-
-    command.stdout(Stdio::null());
-    command.stderr(Stdio::null());
-    let status = command.status().await?;
-
-    if !status.success() {
-        return Err(anyhow::anyhow!(
-            "Failed to clone templates from GitHub. Exit code: {}",
-            status.code().unwrap_or(-1)
-        ));
-    }
-
-    let repo_templates_dir = temp_dir.join("templates");
-    if !repo_templates_dir.exists() {
-        fs::remove_dir_all(&temp_dir).ok();
-        return Err(anyhow::anyhow!(
-            "No 'templates' directory found in the repository"
-        ));
-    }
-
-    match fs::rename(&repo_templates_dir, templates_dir) {
-        Ok(_) => {}
-        Err(e) if e.raw_os_error() == Some(66) => {
-            println!("{} Rename failed, copying instead...", "⚠️".yellow());
-            copy_dir_recursive(&repo_templates_dir, templates_dir)?;
+    };
+    match installed {
+        Some(installed)
+            if installed.git_ref == latest.git_ref && installed.commit == latest.commit =>
+        {
+            Ok(None)
         }
-        Err(e) => return Err(e.into()),
-    }
-
-    let repo_manifests_dir = temp_dir.join("manifests");
-    if !repo_manifests_dir.exists() {
-        fs::remove_dir_all(&temp_dir).ok();
-        return Err(anyhow::anyhow!(
-            "No 'manifests' directory found in the repository"
-        ));
-    }
-
-    let manifests_dir = templates_dir.parent().unwrap().join("manifests");
-    if manifests_dir.exists() {
-        fs::remove_dir_all(&manifests_dir)?;
+        _ => Ok(Some((old, label(&latest)))),
     }
+}
 
-    match fs::rename(&repo_manifests_dir, &manifests_dir) {
-        Ok(_) => {}
-        Err(e) if e.raw_os_error() == Some(66) => {
-            println!(
-                "{} Rename failed for manifests, copying instead...",
-                "⚠️".yellow()
-            );
-            copy_dir_recursive(&repo_manifests_dir, &manifests_dir)?;
+async fn clone_templates(root: &Path) -> Result<()> {
+    let commit_id = get_git_commit_id().await?;
+    let templates_dir = root.join("templates");
+
+    match read_commit_file(&templates_dir)? {
+        Some(CommitFile {
+            commit: Some(local),
+            ..
+        }) if local.id == commit_id => {
+            println!("{} Templates are up to date", "✅".green());
+            return Ok(());
         }
-        Err(e) => return Err(e.into()),
+        Some(_) => {}
+        None => println!("{} No local commit ID found", "🔍".yellow()),
     }
 
-    let commit_file = templates_dir.join("commit.toml");
-    let commit_data = format!("[commit]\nid = \"{}\"\n", commit_id);
-    fs::write(&commit_file, commit_data)?;
+    let branch = Mirrors::load().templates_branch;
+    let template_commit = templates::fetch(root, &branch).await?;
+    let commit_file = CommitFile {
+        commit: Some(CommitId { id: commit_id }),
+        template: Some(TemplatesVersion {
+            git_ref: branch,
+            commit: template_commit,
+        }),
+    };
+    fs::write(
+        templates_dir.join("commit.toml"),
+        toml::to_string(&commit_file)?,
+    )?;
 
-    fs::remove_dir_all(&temp_dir)?;
     println!(
         "{} Templates and manifests downloaded successfully",
         "✓".green()
@@ -227,7 +496,7 @@ async fn clone_templates(templates_dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn copy_dir_recursive(src: &PathBuf, dst: &PathBuf) -> Result<()> {
+pub fn copy_dir_recursive(src: &PathBuf, dst: &PathBuf) -> Result<()> {
     fs::create_dir_all(dst)?;
 
     for entry in fs::read_dir(src)? {
@@ -246,13 +515,34 @@ fn copy_dir_recursive(src: &PathBuf, dst: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn update_templates(templates_dir: &PathBuf) -> Result<()> {
-    clone_templates(templates_dir).await
+async fn update_templates(root: &Path) -> Result<()> {
+    clone_templates(root).await
 }
 
-pub async fn download_toolchain_files(cache_dir: &PathBuf) -> Result<()> {
+pub async fn download_toolchain_files(cache_dir: &Path, source: &Source) -> Result<()> {
     let toolchain_dir = cache_dir.join("toolchains");
 
+    if let Source::Bundle(bundle_dir) = source {
+        println!("{} Copying channel manifests from bundle...", "📦".green());
+        fs::create_dir_all(&toolchain_dir)?;
+        for entry in fs::read_dir(&toolchain_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        for entry in fs::read_dir(bundle_dir.join("toolchains"))
+            .context("Bundle has no 'toolchains' directory")?
+        {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                fs::copy(entry.path(), toolchain_dir.join(entry.file_name()))?;
+            }
+        }
+        println!("{} Toolchain files setup complete", "✅".green());
+        return Ok(());
+    }
+
     if has_existing_toolchain_files(&toolchain_dir).await? {
         println!(
             "{} Existing toolchain files found, updating...",
@@ -284,111 +574,100 @@ async fn has_existing_toolchain_files(toolchain_dir: &PathBuf) -> Result<bool> {
     Ok(false)
 }
 
-async fn update_toolchain_files(toolchain_dir: &PathBuf) -> Result<()> {
+async fn update_toolchain_files(toolchain_dir: &Path) -> Result<()> {
     clone_toolchain_files(toolchain_dir).await
 }
 
-async fn clone_toolchain_files(toolchain_dir: &PathBuf) -> Result<()> {
-    if toolchain_dir.exists() {
-        fs::remove_dir_all(toolchain_dir)?;
-    }
-    fs::create_dir_all(toolchain_dir)?;
-
+async fn clone_toolchain_files(toolchain_dir: &Path) -> Result<()> {
     println!(
         "{} Fetching latest channel manifests from GitHub releases...",
         "⬇️".green()
     );
-
-    async fn get_latest_manifest(channel: &str, toolchain_dir: &PathBuf) -> Result<()> {
-        let manifest_file = format!("{}-manifest.toml", channel);
-        let output_file = toolchain_dir.join(format!("channel-nockup-{}.toml", channel));
-
-        println!("{} Fetching latest {} manifest...", "🔍".yellow(), channel);
-
-        let api_url = "https://api.github.com/repos/nockchain/nockchain/releases";
-        let client = reqwest::Client::new();
-        let response = client
-            .get(api_url)
-            .header("User-Agent", "nockup")
-            .send()
-            .await
-            .context("Failed to fetch releases from GitHub API")?;
-
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to fetch releases: HTTP {}",
-                response.status()
-            ));
+    let index = ReleaseIndex::fetch().await?;
+
+    // Every published channel, plus the configured one if it pins a build.
+    let mut specs: Vec<ChannelSpec> = index
+        .channels()
+        .into_iter()
+        .map(|channel| ChannelSpec { channel, pin: None })
+        .collect();
+    let configured = NockupConfig::load()
+        .ok()
+        .and_then(|config| ChannelSpec::parse(&config.channel).ok());
+    if let Some(configured) = configured {
+        if !specs.contains(&configured) {
+            specs.push(configured);
         }
+    }
 
-        let releases: serde_json::Value = response
-            .json()
-            .await
-            .context("Failed to parse releases JSON")?;
-
-        let latest_tag = get_git_commit_id().await?;
-
-        let manifest_url = format!(
-            "https://github.com/nockchain/nockchain/releases/download/{}-build-{}/{}",
-            channel, latest_tag, manifest_file
-        );
-
-        println!("{} Downloading from: {}", "⬇️".blue(), manifest_url);
-
-        let response = client
-            .get(&manifest_url)
-            .header("User-Agent", "nockup")
-            .send()
-            .await
-            .context("Failed to download manifest")?;
-
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to download manifest: HTTP {}",
-                response.status()
-            ));
-        }
-
-        let content = response
-            .text()
-            .await
-            .context("Failed to read manifest content")?;
-
-        tokio_fs::write(&output_file, content)
-            .await
-            .context("Failed to write manifest file")?;
-
-        println!(
-            "{} Downloaded: channel-nockup-{}.toml",
-            "✅".green(),
-            channel
-        );
+    write_channel_manifests(toolchain_dir, &specs, &index).await
+}
 
-        Ok(())
+/// Replace the channel manifests in `toolchain_dir` with those of the builds
+/// `specs` select, saved as `channel-nockup-<spec>.toml`.
+pub async fn write_channel_manifests(
+    toolchain_dir: &Path,
+    specs: &[ChannelSpec],
+    index: &ReleaseIndex,
+) -> Result<()> {
+    if specs.is_empty() {
+        return Err(anyhow::anyhow!("The release index lists no channels"));
     }
 
-    let channels = ["stable", "nightly"];
-    let mut errors = Vec::new();
-
-    for channel in &channels {
-        if let Err(e) = get_latest_manifest(channel, toolchain_dir).await {
+    // Fetch every manifest before replacing any, so a refresh that fails
+    // entirely leaves the existing ones in place.
+    let results: Vec<Result<String>> = stream::iter(specs)
+        .map(|spec| async move {
+            println!("{} Fetching {} manifest...", "🔍".yellow(), spec);
+            let commit = channel::resolve_commit(spec, Some(index)).await?;
+            fetch_channel_manifest(&spec.channel, &commit).await
+        })
+        .buffered(CONCURRENT_DOWNLOADS)
+        .collect()
+        .await;
+
+    let errors: Vec<String> = specs
+        .iter()
+        .zip(&results)
+        .filter_map(|(spec, result)| {
+            let e = result.as_ref().err()?;
             println!(
                 "{} Failed to download {} manifest: {}",
                 "⚠️".yellow(),
-                channel,
+                spec,
                 e
             );
-            errors.push(format!("{}: {}", channel, e));
-        }
-    }
+            Some(format!("{}: {}", spec, e))
+        })
+        .collect();
 
-    if errors.len() == channels.len() {
+    if errors.len() == specs.len() {
         return Err(anyhow::anyhow!(
             "Failed to download any toolchain manifests: {}",
             errors.join(", ")
         ));
     }
 
+    // Installed toolchains live alongside the channel manifests, so only the
+    // manifests themselves are replaced here.
+    fs::create_dir_all(toolchain_dir)?;
+    for entry in fs::read_dir(toolchain_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    for (spec, result) in specs.iter().zip(results) {
+        let Ok(content) = result else {
+            continue;
+        };
+        let output_file = toolchain_dir.join(format!("channel-nockup-{}.toml", spec));
+        tokio_fs::write(&output_file, content)
+            .await
+            .context("Failed to write manifest file")?;
+        println!("{} Downloaded: channel-nockup-{}.toml", "✅".green(), spec);
+    }
+
     if !errors.is_empty() {
         println!(
             "{} Some manifests failed to download: {}",
@@ -401,186 +680,467 @@ async fn clone_toolchain_files(toolchain_dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub async fn download_binaries(config: &toml::Value) -> Result<()> {
-    let channel = config["channel"]
-        .as_str()
-        .ok_or_else(|| anyhow::anyhow!("Invalid channel in config"))?;
-    let architecture = config["architecture"]
-        .as_str()
-        .ok_or_else(|| anyhow::anyhow!("Invalid architecture in config"))?;
+/// Download the channel manifest published with the `{channel}-build-{commit}`
+/// release.  The manifest is stamped with the commit it was fetched for, since
+/// collated manifests do not carry one themselves.
+pub async fn fetch_channel_manifest(channel: &str, commit: &str) -> Result<String> {
+    let manifest_url = format!(
+        "{}/{}-build-{}/{}-manifest.toml",
+        Mirrors::load().releases,
+        channel,
+        commit,
+        channel
+    );
+
+    println!("{} Downloading from: {}", "⬇️".blue(), manifest_url);
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(&manifest_url)
+        .header("User-Agent", "nockup")
+        .send()
+        .await
+        .context("Failed to download manifest")?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to download manifest: HTTP {}",
+            response.status()
+        ));
+    }
 
-    let cache_dir = get_cache_dir()?;
+    let content = response
+        .text()
+        .await
+        .context("Failed to read manifest content")?;
+
+    let mut manifest = ChannelManifest::parse(&content)?;
+    if manifest.commit.is_none() {
+        manifest.commit = Some(commit.to_string());
+        manifest.release_tag = Some(format!("{}-build-{}", channel, commit));
+    }
+    Ok(toml::to_string(&manifest)?)
+}
+
+pub fn read_channel_manifest(path: &Path) -> Result<ChannelManifest> {
+    let manifest = std::fs::read_to_string(path).context(format!(
+        "Failed to read channel manifest at path {}",
+        path.display()
+    ))?;
+    ChannelManifest::parse(&manifest)
+        .map_err(|e| anyhow::anyhow!("{} at path {}", e, path.display()))
+}
+
+/// Install the configured channel's build of every component in the
+/// configured profile, leaving unchanged components as they are.
+pub async fn download_binaries(config: &NockupConfig, source: &Source) -> Result<()> {
+    let channel = config.channel.as_str();
+    let architecture = config.architecture.as_str();
+
+    let cache_dir = config::nockup_home()?;
     let channel_name = format!("channel-nockup-{}", channel);
     let manifest_path = cache_dir
         .join("toolchains")
         .join(format!("{}.toml", channel_name));
-    let manifest = std::fs::read_to_string(&manifest_path).context(format!(
-        "Failed to read channel manifest for '{}.toml' at path {}",
-        channel_name,
-        manifest_path.display()
-    ))?;
-    let manifest: toml::Value = toml::de::from_str(&manifest).context(format!(
-        "Failed to parse channel manifest for '{}'",
-        channel_name
-    ))?;
+    let manifest = read_channel_manifest(&manifest_path)?;
+    let toolchain = toolchain::toolchain_for_manifest(channel, &manifest)?;
+    let profile = config.profile();
+    let packages = manifest.profile_packages(profile)?;
+    let status = Status::load()?;
+    let update_nockup = packages.contains(&"nockup")
+        && manifest
+            .target("nockup", architecture)
+            .is_ok_and(|target| target.available && status.reusable("nockup", target).is_none());
 
     println!(
-        "{} Downloading binaries for channel '{}' and architecture '{}'...",
+        "{} Downloading binaries for channel '{}' and architecture '{}' (profile '{}')...",
         "⬇️".green(),
         channel_name.cyan(),
-        architecture.cyan()
+        architecture.cyan(),
+        profile.cyan()
     );
 
-    for index in ["hoon", "hoonc", "nockup"] {
-        println!("{} Downloading {} binary...", "⬇️".green(), index.cyan());
-        let archive_url = manifest["pkg"][index]["target"][architecture]["url"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("{} Invalid URL for {} binary", "❌".red(), index))?;
-        let archive_url = archive_url.replace("http://", "https://");
-        let signature_url = format!("{}.asc", archive_url);
-
-        let archive_blake3 = manifest["pkg"][index]["target"][architecture]["hash_blake3"]
-            .as_str()
-            .ok_or_else(|| {
-                anyhow::anyhow!("{} Invalid Blake3 hash for {} binary", "❌".red(), index)
-            })?;
-        let archive_sha1 = manifest["pkg"][index]["target"][architecture]["hash_sha1"]
-            .as_str()
-            .ok_or_else(|| {
-                anyhow::anyhow!("{} Invalid SHA1 hash for {} binary", "❌".red(), index)
-            })?;
-
-        println!("{} Blake3 checksum passed.", "✅".green());
-        println!("{} SHA1 checksum passed.", "✅".green());
-
-        let archive_path = download_file(&archive_url).await?;
-
-        if std::env::consts::OS == "linux" {
-            let signature_path = download_file(&signature_url).await?;
-            verify_gpg_signature(&archive_path, &signature_path).await?;
-            fs::remove_file(&signature_path)?;
+    // Stage and verify everything before touching the installed generation.
+    let staging_dir = generation::staging_dir()?;
+    let result = async {
+        let staged_toolchain = if toolchain.is_installed()? {
+            println!(
+                "{} Toolchain '{}' is already installed",
+                "✅".green(),
+                toolchain.name().cyan()
+            );
+            None
         } else {
+            Some(
+                toolchain::prepare_staged_toolchain(
+                    &toolchain,
+                    Some(manifest.clone()),
+                    &staging_dir,
+                )
+                .await?,
+            )
+        };
+
+        // The toolchain and nockup are downloaded as one set, though each
+        // goes into its own directory.  Profiles that leave out nockup keep
+        // the installed binary as it is.
+        let toolchain_bin_dir = staged_toolchain
+            .as_ref()
+            .map(|staged| staged.dir.join("bin"));
+        let staged_bin_dir = staging_dir.join("bin");
+        let mut installs: Vec<(&str, &Path)> = Vec::new();
+        if let (Some(staged), Some(bin_dir)) = (&staged_toolchain, &toolchain_bin_dir) {
+            for package in &staged.packages {
+                installs.push((package, bin_dir));
+            }
+        }
+        if update_nockup {
+            installs.push(("nockup", &staged_bin_dir));
+        }
+        install_components_into(&manifest, architecture, &installs, source).await?;
+        let staged_nockup = update_nockup.then(|| staged_bin_dir.join("nockup"));
+
+        if staged_toolchain.is_none()
+            && staged_nockup.is_none()
+            && toolchain::default_toolchain()?.as_deref() == Some(toolchain.name().as_str())
+        {
+            println!("{} Everything is up to date", "✅".green());
+            return Ok(());
+        }
+        if let Some(staged_toolchain) = staged_toolchain {
+            toolchain::activate_staged_toolchain(&toolchain, &staged_toolchain.dir)?;
+        }
+        generation::switch(staged_nockup.as_deref(), &toolchain.name())
+    }
+    .await;
+    let _ = fs::remove_dir_all(&staging_dir);
+    result?;
+
+    let toolchain_bin_dir = toolchain.bin_dir()?;
+    Status::record(
+        &manifest,
+        channel,
+        &toolchain.name(),
+        architecture,
+        &packages,
+        |package| match package {
+            "nockup" => Ok(cache_dir.join("bin").join("nockup")),
+            _ => Ok(toolchain_bin_dir.join(package)),
+        },
+    )
+}
+
+/// Download, verify and extract each of `packages` from `manifest` into
+/// `bin_dir`.  Packages that are not available for `architecture` are skipped,
+/// and intact installed binaries built from the same archive are copied.
+pub async fn install_components(
+    manifest: &ChannelManifest,
+    architecture: &str,
+    packages: &[&str],
+    bin_dir: &Path,
+    source: &Source,
+) -> Result<()> {
+    let packages: Vec<(&str, &Path)> = packages.iter().map(|&package| (package, bin_dir)).collect();
+    install_components_into(manifest, architecture, &packages, source).await
+}
+
+/// Like `install_components`, but with each package going into a `bin`
+/// directory of its own, so that e.g. a toolchain and nockup itself can be
+/// staged together.
+///
+/// Release archives are downloaded concurrently, each only once however many
+/// packages it holds, and nothing is extracted until all of them have
+/// arrived.  Callers install into a staging directory, so a package that
+/// fails leaves nothing behind.
+pub async fn install_components_into(
+    manifest: &ChannelManifest,
+    architecture: &str,
+    packages: &[(&str, &Path)],
+    source: &Source,
+) -> Result<()> {
+    let mirrors = Mirrors::load();
+    let status = Status::load()?;
+    let mut archives: Vec<ReleaseArchive> = Vec::new();
+    for &(index, bin_dir) in packages {
+        let target = manifest.target(index, architecture)?;
+        if !target.available {
             println!(
-                "{} Skipping signature verification on {} (not yet supported)",
+                "{} {} is not available for {} in this release; skipping",
                 "⚠️".yellow(),
-                std::env::consts::OS
+                index.cyan(),
+                architecture
             );
+            continue;
         }
 
-        verify_checksums(&archive_path, &archive_blake3, &archive_sha1).await?;
+        if let Some(installed) = status.reusable(index, target) {
+            println!(
+                "{} {} is unchanged; reusing the installed binary",
+                "♻️".green(),
+                index.cyan()
+            );
+            fs::create_dir_all(bin_dir)?;
+            fs::copy(installed, bin_dir.join(index))
+                .with_context(|| format!("Failed to copy {}", installed.display()))?;
+            // Carry over the signature recorded when the binary was first installed.
+            let signature = installed
+                .parent()
+                .and_then(Path::parent)
+                .and_then(|dir| Receipt::load(dir).ok().flatten())
+                .and_then(|receipt| receipt.components.get(index).cloned())
+                .filter(|component| component.archive_blake3 == target.hash_blake3)
+                .and_then(|component| component.signature);
+            record_receipt(
+                manifest,
+                architecture,
+                index,
+                bin_dir,
+                &target.url,
+                signature,
+            )?;
+            continue;
+        }
 
-        let target_dir = get_cache_dir()?;
-        let binary_path = target_dir.join("bin");
-        fs::create_dir_all(&binary_path)?;
+        let url = mirrors.artifact_url(&target.url);
+        match archives.iter_mut().find(|archive| archive.url == url) {
+            Some(archive) => archive.packages.push((index, target, bin_dir)),
+            None => archives.push(ReleaseArchive {
+                url,
+                packages: vec![(index, target, bin_dir)],
+            }),
+        }
+    }
+
+    let signed = is_signed(architecture);
+    for archive in &archives {
+        let names: Vec<String> = archive
+            .packages
+            .iter()
+            .map(|(name, _, _)| name.cyan().to_string())
+            .collect();
+        let noun = if names.len() == 1 {
+            "binary"
+        } else {
+            "binaries"
+        };
+        println!(
+            "{} Downloading {} {}...",
+            "⬇️".green(),
+            names.join(", "),
+            noun
+        );
+    }
+    let downloads: Vec<(Artifact, Option<Artifact>)> = stream::iter(&archives)
+        .map(|archive| async move {
+            let artifact = source.fetch_artifact(&archive.url).await?;
+            let signature = if signed {
+                Some(
+                    source
+                        .fetch_artifact(&format!("{}.asc", archive.url))
+                        .await?,
+                )
+            } else {
+                None
+            };
+            Ok::<_, anyhow::Error>((artifact, signature))
+        })
+        .buffered(CONCURRENT_DOWNLOADS)
+        .try_collect()
+        .await?;
+
+    // Verify and extract one archive at a time so each one's output stays together.
+    for (archive, (artifact, signature)) in archives.iter().zip(downloads) {
+        let signature = match signature {
+            Some(signature) => Some(verify_signature(&artifact.path, &signature.path)?),
+            None => {
+                println!(
+                    "{} Skipping signature verification for {} (not yet supported)",
+                    "⚠️".yellow(),
+                    architecture
+                );
+                None
+            }
+        };
 
-        extract_binary_from_archive(&archive_path, &binary_path, index).await?;
+        let (_, first, _) = archive.packages[0];
+        verify_checksums(&artifact.hashes, &first.hash_blake3, &first.hash_sha1)?;
+        for (_, target, _) in &archive.packages[1..] {
+            if !target.hash_blake3.eq_ignore_ascii_case(&first.hash_blake3)
+                || !target.hash_sha1.eq_ignore_ascii_case(&first.hash_sha1)
+            {
+                verify_checksums(&artifact.hashes, &target.hash_blake3, &target.hash_sha1)?;
+            }
+        }
 
-        fs::remove_file(&archive_path)?;
+        for &(index, _, bin_dir) in &archive.packages {
+            fs::create_dir_all(bin_dir)?;
+            extract_binary_from_archive(&artifact.path, bin_dir, index).await?;
+            record_receipt(
+                manifest,
+                architecture,
+                index,
+                bin_dir,
+                &archive.url,
+                signature.clone(),
+            )?;
+        }
     }
 
     Ok(())
 }
 
-async fn verify_gpg_signature(
-    archive_path: &std::path::Path,
-    signature_path: &std::path::Path,
-) -> Result<()> {
-    println!("{} Verifying GPG signature...", "🔐".yellow());
+/// A release archive to download and the packages to extract from it, each
+/// with the directory it goes into.
+struct ReleaseArchive<'a> {
+    url: String,
+    packages: Vec<(&'a str, &'a Target, &'a Path)>,
+}
 
-    if !archive_path.exists() {
-        return Err(anyhow::anyhow!(
-            "Archive file does not exist: {}",
-            archive_path.display()
-        ));
-    }
-    if !signature_path.exists() {
-        return Err(anyhow::anyhow!(
-            "Signature file does not exist: {}",
-            signature_path.display()
-        ));
-    }
+/// Record `package`, just installed into `bin_dir`, in the receipt beside it.
+fn record_receipt(
+    manifest: &ChannelManifest,
+    architecture: &str,
+    package: &str,
+    bin_dir: &Path,
+    url: &str,
+    signature: Option<String>,
+) -> Result<()> {
+    let target = manifest.target(package, architecture)?;
+    let hashes = hash_file(&bin_dir.join(package))?;
+    let receipt_dir = bin_dir
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("{} has no parent directory", bin_dir.display()))?;
+    Receipt::add(
+        receipt_dir,
+        package,
+        ComponentReceipt {
+            version: manifest.pkg[package].version.clone(),
+            commit: manifest.commit.clone(),
+            architecture: architecture.to_string(),
+            url: url.to_string(),
+            archive_blake3: target.hash_blake3.clone(),
+            archive_sha1: target.hash_sha1.clone(),
+            blake3: hashes.blake3,
+            sha1: hashes.sha1,
+            signature,
+            installed: receipt::now(),
+        },
+    )
+}
 
-    let output = Command::new("gpg")
-        .args(["--verify", signature_path.to_str().unwrap(), archive_path.to_str().unwrap()])
-        .output()
-        .await
-        .context("Failed to execute gpg command")?;
+/// Whether release archives for `architecture` are signed.  Only Linux builds
+/// are, so macOS and Windows archives are checked by hash alone.  This goes by
+/// the target rather than the host, as builds may be for another machine.
+fn is_signed(architecture: &str) -> bool {
+    !architecture.contains("-apple-") && !architecture.contains("-windows")
+}
 
-    if output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if stderr.contains("Good signature") {
-            println!("{} GPG signature verified successfully", "✅".green());
-            return Ok(());
+/// Download the release archives and signatures of `packages` for
+/// `architecture` into the bundle at `bundle_dir`, verifying their checksums.
+pub async fn bundle_components(
+    manifest: &ChannelManifest,
+    architecture: &str,
+    packages: &[&str],
+    bundle_dir: &Path,
+) -> Result<()> {
+    fs::create_dir_all(bundle_dir.join("releases"))?;
+    let mirrors = Mirrors::load();
+    for &index in packages {
+        let target = manifest.target(index, architecture)?;
+        if !target.available {
+            println!(
+                "{} {} is not available for {} in this release; skipping",
+                "⚠️".yellow(),
+                index.cyan(),
+                architecture
+            );
+            continue;
         }
-    }
+        let archive_url = mirrors.artifact_url(&target.url);
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    if stderr.contains("No public key") {
         println!(
-            "{} Public key not found, importing from keyserver...",
-            "🔑".yellow()
+            "{} Bundling {} for {}...",
+            "⬇️".green(),
+            index.cyan(),
+            architecture.cyan()
         );
+        let archive = download_file(&archive_url).await?;
+        verify_checksums(&archive.hashes, &target.hash_blake3, &target.hash_sha1)?;
+        move_file(
+            &archive.path,
+            &bundle_artifact_path(bundle_dir, &archive_url),
+        )?;
+
+        if is_signed(architecture) {
+            let signature_url = format!("{}.asc", archive_url);
+            let signature = download_file(&signature_url).await?;
+            move_file(
+                &signature.path,
+                &bundle_artifact_path(bundle_dir, &signature_url),
+            )?;
+        }
+    }
+    Ok(())
+}
 
-        let import_output = Command::new("gpg")
-            .args(["--keyserver", "keyserver.ubuntu.com", "--recv-keys", "A6FFD2DB7D4C9710"])
-            .output()
-            .await
-            .context("Failed to import public key from keyserver")?;
-
-        if !import_output.status.success() {
-            let alt_import = Command::new("gpg")
-                .args(["--keyserver", "keys.openpgp.org", "--recv-keys", "A6FFD2DB7D4C9710"])
-                .output()
-                .await;
+/// Replace the executable at `target` with `new_exe`.  If `target` is the
+/// running nockup, it is swapped out in a way that works while it is busy
+/// (including on Windows, where a running executable cannot be overwritten).
+pub fn replace_executable(new_exe: &Path, target: &Path) -> Result<()> {
+    let running = std::env::current_exe()
+        .and_then(|exe| exe.canonicalize())
+        .ok();
+    let is_running = target.exists() && target.canonicalize().ok() == running;
+    if is_running {
+        self_replace::self_replace(new_exe)
+            .with_context(|| format!("Failed to replace {}", target.display()))?;
+        fs::remove_file(new_exe)?;
+    } else {
+        move_file(new_exe, target)
+            .with_context(|| format!("Failed to install {}", target.display()))?;
+    }
+    Ok(())
+}
 
-            if let Ok(alt_output) = alt_import {
-                if !alt_output.status.success() {
-                    return Err(anyhow::anyhow!(
-                        "Failed to import public key from keyservers. Please import manually:\n  gpg --keyserver keyserver.ubuntu.com --recv-keys A6FFD2DB7D4C9710"
-                    ));
-                }
-            } else {
-                return Err(anyhow::anyhow!(
-                    "Failed to import public key. Please import manually:\n  gpg --keyserver keyserver.ubuntu.com --recv-keys A6FFD2DB7D4C9710"
-                ));
-            }
-        }
+fn move_file(src: &Path, dst: &Path) -> Result<()> {
+    if fs::rename(src, dst).is_err() {
+        fs::copy(src, dst)
+            .with_context(|| format!("Failed to copy {} to {}", src.display(), dst.display()))?;
+        fs::remove_file(src)?;
+    }
+    Ok(())
+}
 
-        println!("{} Public key imported successfully", "✅".green());
+/// Verify the detached signature at `signature_path` over the archive against
+/// the trusted keyring, returning the fingerprint of the key that made it.
+fn verify_signature(archive_path: &Path, signature_path: &Path) -> Result<String> {
+    println!("{} Verifying signature...", "🔐".yellow());
 
-        let retry_output = Command::new("gpg")
-            .args([
-                "--verify",
-                "--verbose",
-                signature_path.to_str().unwrap(),
-                archive_path.to_str().unwrap(),
-            ])
-            .output()
-            .await
-            .context("Failed to execute gpg verification after key import")?;
+    let keyring = pgp::trusted_keyring()?;
+    let signature = fs::read(signature_path)
+        .with_context(|| format!("Failed to read {}", signature_path.display()))?;
+    let archive = fs::File::open(archive_path)
+        .with_context(|| format!("Failed to open {}", archive_path.display()))?;
 
-        if !retry_output.status.success() {
-            let retry_stderr = String::from_utf8_lossy(&retry_output.stderr);
-            return Err(anyhow::anyhow!(
-                "GPG signature verification failed after key import: {}", retry_stderr
-            ));
+    match keyring.verify(&signature, archive) {
+        Ok(verified) => {
+            println!(
+                "{} Good signature from {} made {}",
+                "✅".green(),
+                verified.user_id.as_deref().unwrap_or(&verified.fingerprint),
+                pgp::format_time(verified.created)
+            );
+            Ok(verified.fingerprint)
         }
-
-        let retry_stderr = String::from_utf8_lossy(&retry_output.stderr);
-        if retry_stderr.contains("Good signature") {
-            println!("{} GPG signature verified successfully", "✅".green());
-        } else {
-            return Err(anyhow::anyhow!(
-                "GPG signature verification failed: {}", retry_stderr
-            ));
+        Err(e) => {
+            println!(
+                "{} Signature verification failed for {}",
+                "❌".red(),
+                archive_path.display()
+            );
+            Err(e.into())
         }
-    } else {
-        return Err(anyhow::anyhow!(
-            "GPG signature verification failed: {}", stderr
-        ));
     }
-
-    Ok(())
 }
 
 async fn extract_binary_from_archive(
@@ -642,103 +1202,18 @@ async fn extract_binary_from_archive(
 
     if !found_binary {
         return Err(anyhow::anyhow!(
-            "Binary '{}' not found in archive", binary_name
+            "Binary '{}' not found in archive",
+            binary_name
         ));
     }
 
     Ok(())
 }
 
-async fn download_file(url: &str) -> Result<PathBuf> {
-    let response = reqwest::get(url)
-        .await
-        .context(format!("Failed to download file from '{}'", url))?;
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to download file from '{}': HTTP {}",
-            url,
-            response.status()
-        ));
-    }
-
-    let url_filename = url.split('/').last().unwrap_or("download");
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let filename = format!("nockup_{}_{}", timestamp, url_filename);
-    let temp_file = std::env::temp_dir().join(filename);
-
-    let mut file = std::fs::File::create(&temp_file).context("Failed to create temporary file")?;
-    let content = response.bytes().await?;
-    std::io::copy(&mut content.as_ref(), &mut file).context("Failed to write to temporary file")?;
-    Ok(temp_file)
-}
-
-async fn verify_checksums(
-    file_path: &PathBuf,
-    expected_blake3: &str,
-    expected_sha1: &str,
-) -> Result<()> {
-    let bytes =
-        std::fs::read(file_path).context("Failed to read file for checksum verification")?;
-
-    let computed_blake3 = blake3::hash(&bytes);
-    if computed_blake3.to_string() != expected_blake3 {
-        return Err(anyhow::anyhow!(
-            "Checksum verification failed: expected {}, got {}", expected_blake3, computed_blake3
-        ));
-    }
-
-    let mut hasher = Sha1::new();
-    hasher.update(&bytes);
-    let computed_sha1 = hasher.finalize();
-    let expected_sha1: [u8; 20] = hex::decode(expected_sha1)
-        .map_err(|e| anyhow::anyhow!("Invalid hex SHA-1: {}", e))?
-        .try_into()
-        .map_err(|_| anyhow!("Failed to convert to fixed array (length mismatch)"))?;
-    if computed_sha1.as_slice() != &expected_sha1 {
-        let expected_hex = hex::encode(&expected_sha1);
-        let computed_hex = hex::encode(computed_sha1.as_slice());
-        return Err(anyhow::anyhow!(
-            "Checksum verification failed: expected {}, got {}", expected_hex, computed_hex
-        ));
-    }
-    Ok(())
-}
-
-pub async fn write_commit_details(cache_dir: &PathBuf) -> Result<()> {
-    let status_file = cache_dir.join("status.toml");
-    let mut config = toml::map::Map::new();
-    config.insert("commit".into(), toml::Value::Table(toml::map::Map::new()));
-    let commit_id = get_git_commit_id().await?;
-    let commit_table = config
-        .get_mut("commit")
-        .and_then(|commit| commit.as_table_mut())
-        .ok_or_else(|| anyhow::anyhow!("Failed to insert commit ID into config"))?;
-    commit_table.insert("id".into(), toml::Value::String(commit_id));
-    fs::write(status_file, toml::to_string(&config)?).context("Failed to write config file")?;
-    Ok(())
-}
-
-async fn get_git_commit_id() -> Result<String> {
-    let repo_url = format!("https://api.github.com/repos/nockchain/nockchain/commits/master");
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&repo_url)
-        .header("User-Agent", "nockup")
-        .send()
-        .await
-        .context("Failed to fetch commit ID from GitHub")?;
-
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to fetch commit ID: HTTP {}",
-            response.status()
-        ));
-    }
-
-    let json: serde_json::Value = response.json().await.context("Invalid JSON response")?;
+/// The latest commit of the nockchain repository.
+pub async fn get_git_commit_id() -> Result<String> {
+    let repo_url = format!("{}/commits/master", Mirrors::load().api);
+    let json: serde_json::Value = github::get_json(&repo_url, "commit ID").await?;
     let commit_id = json["sha"]
         .as_str()
         .ok_or_else(|| anyhow::anyhow!("Missing commit ID in response"))?;
@@ -0,0 +1,157 @@
+//! Shell completions.
+//!
+//! Completion is dynamic: the script printed by `nockup completions <shell>`
+//! calls back into nockup with `COMPLETE=<shell>` set, so arguments can be
+//! completed from what is installed, e.g. cached channels and toolchains.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use clap_complete::engine::{CompletionCandidate, PathCompleter, ValueCompleter};
+use clap_complete::env::Shells;
+
+use super::common;
+use crate::cli::CompletionShell;
+use crate::config::{self, NockupConfig};
+use crate::toolchain;
+
+/// Environment variable that switches nockup into completion mode.
+pub const COMPLETE_ENV: &str = "COMPLETE";
+
+/// Print the script that registers nockup's completions with `shell`.
+pub fn run(shell: CompletionShell) -> Result<()> {
+    let name = shell
+        .to_possible_value()
+        .expect("completion shells have names")
+        .get_name()
+        .to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .ok_or_else(|| anyhow::anyhow!("Completions are not supported for {}", name))?;
+    completer.write_registration(
+        COMPLETE_ENV,
+        "nockup",
+        "nockup",
+        "nockup",
+        &mut std::io::stdout().lock(),
+    )?;
+    Ok(())
+}
+
+/// Project config files in the current directory, as accepted by `nockup
+/// init`, with the template each one uses.
+pub fn project_configs() -> Vec<CompletionCandidate> {
+    let templates_dir = common::templates_dir().ok();
+    let Ok(entries) = fs::read_dir(".") else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<CompletionCandidate> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "toml" {
+                return None;
+            }
+            let config: toml::Value = toml::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            let template = config.get("project")?.get("template")?.as_str()?;
+            let installed = templates_dir
+                .as_ref()
+                .is_some_and(|dir| dir.join(template).is_dir());
+            let help = if installed {
+                format!("template '{}'", template)
+            } else {
+                format!("template '{}' (not installed)", template)
+            };
+            Some(CompletionCandidate::new(path.file_stem()?).help(Some(help.into())))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.get_value().cmp(b.get_value()));
+    candidates
+}
+
+/// Directories, as accepted by `nockup build` and `nockup run`.  Those holding
+/// a NockApp project, i.e. a `manifest.toml`, are offered first; the rest are
+/// still offered so that nested projects can be reached.
+pub fn project_dirs(current: &OsStr) -> Vec<CompletionCandidate> {
+    let mut candidates: Vec<CompletionCandidate> = PathCompleter::dir()
+        .complete(current)
+        .into_iter()
+        .map(|candidate| {
+            let path = Path::new(candidate.get_value());
+            let path = match path.strip_prefix("~") {
+                Ok(rest) => dirs::home_dir().map(|home| home.join(rest)),
+                Err(_) => Some(path.to_path_buf()),
+            };
+            if path.is_some_and(|path| path.join("manifest.toml").is_file()) {
+                candidate
+                    .help(Some("NockApp project".into()))
+                    .display_order(Some(0))
+            } else {
+                candidate.display_order(Some(1))
+            }
+        })
+        .collect();
+    candidates.sort_by_key(|candidate| candidate.get_display_order());
+    candidates
+}
+
+/// Channels with a cached channel manifest in `~/.nockup/toolchains`.
+pub fn channels() -> Vec<CompletionCandidate> {
+    let configured = NockupConfig::load().ok().map(|config| config.channel);
+    let Ok(entries) = toolchain::toolchains_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) else {
+        return Vec::new();
+    };
+
+    let mut channels: Vec<String> = entries
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name();
+            let channel = file_name
+                .to_str()?
+                .strip_prefix("channel-nockup-")?
+                .strip_suffix(".toml")?;
+            Some(channel.to_string())
+        })
+        .collect();
+    channels.sort();
+    channels
+        .into_iter()
+        .map(|channel| {
+            let help = (configured.as_ref() == Some(&channel)).then(|| "default".into());
+            CompletionCandidate::new(channel).help(help)
+        })
+        .collect()
+}
+
+/// Keys accepted by `nockup config`.
+pub fn config_keys() -> Vec<CompletionCandidate> {
+    config::SETTINGS
+        .iter()
+        .map(|setting| CompletionCandidate::new(setting.key).help(Some(setting.description.into())))
+        .collect()
+}
+
+/// Installed toolchains.
+pub fn toolchains() -> Vec<CompletionCandidate> {
+    let default = toolchain::default_toolchain().ok().flatten();
+    toolchain::installed_toolchains()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| {
+            let help = (default.as_ref() == Some(&name)).then(|| "default".into());
+            CompletionCandidate::new(name).help(help)
+        })
+        .collect()
+}
+
+/// Target triples added with `nockup target add`.
+pub fn targets() -> Vec<CompletionCandidate> {
+    super::target::installed_targets()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
@@ -0,0 +1,218 @@
+//! `nockup config`: reading and changing settings.
+//!
+//! A setting's effective value comes from, in order of precedence, its
+//! environment variable, the project's `nockup-toolchain.toml` (for the
+//! channel), the global `config.toml`, and finally its default.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::ConfigAction;
+use crate::config::{self, Kind, NockupConfig, Setting, SETTINGS};
+use crate::toolchain::{self, ToolchainSpec};
+
+pub async fn run(action: ConfigAction) -> Result<()> {
+    match action {
+        ConfigAction::Get { key } => get(&key),
+        ConfigAction::Set { key, values } => set(&key, &values),
+        ConfigAction::Unset { key } => unset(&key),
+        ConfigAction::List => list(),
+    }
+}
+
+/// Where a setting's effective value comes from.
+enum Origin {
+    Default,
+    Global(PathBuf),
+    Project(PathBuf),
+    Env(&'static str),
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::Global(path) => write!(f, "global {}", path.display()),
+            Origin::Project(path) => write!(f, "project {}", path.display()),
+            Origin::Env(name) => write!(f, "env {}", name),
+        }
+    }
+}
+
+/// The configuration layers below the environment.
+struct Layers {
+    config: NockupConfig,
+    config_path: PathBuf,
+    /// The keys actually present in `config.toml`.
+    global: toml::Table,
+    project: Option<(PathBuf, ToolchainSpec)>,
+}
+
+impl Layers {
+    fn load() -> Result<Self> {
+        let config = NockupConfig::load_or_default()?;
+        let config_path = config::config_path()?;
+        let global = std::fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let cwd = std::env::current_dir()?;
+        let project = match cwd
+            .ancestors()
+            .find(|dir| dir.join(toolchain::TOOLCHAIN_FILE).exists())
+        {
+            Some(dir) => toolchain::load_project_toolchain(dir)?
+                .map(|spec| (dir.join(toolchain::TOOLCHAIN_FILE), spec)),
+            None => None,
+        };
+        Ok(Layers {
+            config,
+            config_path,
+            global,
+            project,
+        })
+    }
+
+    /// The effective value of `setting` and where it comes from, or `None`
+    /// if it is not set anywhere and has no default.
+    fn resolve(&self, setting: &Setting) -> Result<Option<(toml::Value, Vec<Origin>)>> {
+        let configured = self.config.get(setting.key)?;
+        let global = config::lookup(&self.global, setting.key)
+            .map(|_| Origin::Global(self.config_path.clone()));
+
+        if let Some(env) = setting.env {
+            if let Some(value) = std::env::var_os(env).filter(|value| !value.is_empty()) {
+                if let Kind::Paths = setting.kind {
+                    // Trusted key files from the environment add to the configured ones.
+                    let mut paths = match configured {
+                        Some(toml::Value::Array(paths)) => paths,
+                        _ => Vec::new(),
+                    };
+                    paths.extend(
+                        std::env::split_paths(&value)
+                            .filter(|path| !path.as_os_str().is_empty())
+                            .map(|path| path.display().to_string().into()),
+                    );
+                    let origins = global.into_iter().chain([Origin::Env(env)]).collect();
+                    return Ok(Some((toml::Value::Array(paths), origins)));
+                }
+                let value = value.to_string_lossy().to_string();
+                return Ok(Some((value.into(), vec![Origin::Env(env)])));
+            }
+        }
+
+        if setting.key == "channel" {
+            if let Some((
+                path,
+                ToolchainSpec {
+                    channel: Some(channel),
+                    ..
+                },
+            )) = &self.project
+            {
+                return Ok(Some((
+                    channel.as_str().into(),
+                    vec![Origin::Project(path.clone())],
+                )));
+            }
+        }
+
+        if let (Some(value), Some(global)) = (configured, global) {
+            return Ok(Some((value, vec![global])));
+        }
+        Ok((setting.default)().map(|value| (value, vec![Origin::Default])))
+    }
+}
+
+fn get(key: &str) -> Result<()> {
+    let setting = config::setting(key)?;
+    let Some((value, _)) = Layers::load()?.resolve(setting)? else {
+        return Err(anyhow::anyhow!("'{}' is not set", key));
+    };
+    match value {
+        toml::Value::String(value) => println!("{}", value),
+        toml::Value::Array(values) => {
+            for value in values {
+                println!("{}", value.as_str().unwrap_or_default());
+            }
+        }
+        value => println!("{}", value),
+    }
+    Ok(())
+}
+
+fn set(key: &str, values: &[String]) -> Result<()> {
+    let setting = config::setting(key)?;
+    let value = setting.parse(values)?;
+    let mut config = NockupConfig::load_or_default()?;
+    config.set(key, value.clone())?;
+    config.save()?;
+
+    println!(
+        "{} Set {} = {} in {}",
+        "✅".green(),
+        key.cyan(),
+        value,
+        config::config_path()?.display()
+    );
+    warn_if_overridden(setting);
+    Ok(())
+}
+
+fn unset(key: &str) -> Result<()> {
+    let setting = config::setting(key)?;
+    let mut config = NockupConfig::load_or_default()?;
+    if !config.unset(key)? {
+        println!("{} {} is not set in config.toml", "💡".blue(), key.cyan());
+        return Ok(());
+    }
+    config.save()?;
+
+    match config.get(key)? {
+        Some(value) => println!(
+            "{} Reset {} to its default, {}",
+            "✅".green(),
+            key.cyan(),
+            value
+        ),
+        None => println!("{} Removed {} from config.toml", "✅".green(), key.cyan()),
+    }
+    warn_if_overridden(setting);
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    let layers = Layers::load()?;
+    for setting in SETTINGS {
+        match layers.resolve(setting)? {
+            Some((value, origins)) => {
+                let origins: Vec<String> = origins.iter().map(Origin::to_string).collect();
+                println!(
+                    "{} = {} {}",
+                    setting.key,
+                    value,
+                    format!("# {}", origins.join(" + ")).dimmed()
+                );
+            }
+            None => println!("{}", format!("# {} is not set", setting.key).dimmed()),
+        }
+    }
+    Ok(())
+}
+
+/// Point out when an environment variable hides the value just written.
+fn warn_if_overridden(setting: &Setting) {
+    let Some(env) = setting.env else {
+        return;
+    };
+    if std::env::var_os(env).is_some_and(|value| !value.is_empty()) {
+        println!(
+            "{} {} is set and takes precedence over config.toml",
+            "⚠️".yellow(),
+            env
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use colored::Colorize;
+
+use super::common::{self, Source};
+use crate::config::{self, NockupConfig};
+use crate::toolchain::{self, TOOLCHAIN_BINARIES};
+use crate::{pgp, proxy, shell, version};
+
+enum Level {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// A problem `nockup doctor --fix` can safely repair.
+#[derive(Clone, Copy)]
+enum Fix {
+    AddToPath,
+    InstallProxies,
+    UpdateTemplates,
+}
+
+struct Finding {
+    level: Level,
+    message: String,
+    hint: Option<String>,
+    fix: Option<Fix>,
+}
+
+impl Finding {
+    fn pass(message: impl Into<String>) -> Self {
+        Finding {
+            level: Level::Pass,
+            message: message.into(),
+            hint: None,
+            fix: None,
+        }
+    }
+
+    fn warn(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding {
+            level: Level::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+            fix: None,
+        }
+    }
+
+    fn fail(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Finding {
+            level: Level::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+            fix: None,
+        }
+    }
+
+    fn fixable(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Check the nockup installation and its environment, printing each result
+/// with a hint for anything that needs attention.  With `fix`, repair the
+/// problems that can be repaired safely.
+pub async fn run(fix: bool) -> Result<()> {
+    let cache_dir = config::nockup_home()?;
+
+    let mut findings = vec![check_config(&cache_dir), check_git(), check_signing_keys()];
+    findings.push(check_path(&cache_dir));
+    findings.push(check_proxies(&cache_dir.join("bin")));
+    findings.extend(check_toolchain().await?);
+    findings.push(check_templates(&cache_dir).await);
+
+    let (mut warnings, mut errors) = (0, 0);
+    for finding in &findings {
+        let symbol = match finding.level {
+            Level::Pass => "✅".green(),
+            Level::Warn => "⚠️".yellow(),
+            Level::Fail => "❌".red(),
+        };
+        println!("{} {}", symbol, finding.message);
+        if matches!(finding.level, Level::Pass) {
+            continue;
+        }
+
+        if let (true, Some(repair)) = (fix, finding.fix) {
+            match apply(repair, &cache_dir).await {
+                Ok(()) => {
+                    println!("   {} Fixed", "🔧".green());
+                    continue;
+                }
+                Err(e) => println!("   {} Fix failed: {}", "🔧".red(), e),
+            }
+        }
+        if let Some(hint) = &finding.hint {
+            println!("   {}", hint.dimmed());
+        }
+        if !fix && finding.fix.is_some() {
+            println!("   {}", "Run 'nockup doctor --fix' to fix this".dimmed());
+        }
+        match finding.level {
+            Level::Warn => warnings += 1,
+            _ => errors += 1,
+        }
+    }
+
+    println!();
+    if errors > 0 {
+        return Err(anyhow::anyhow!(
+            "nockup doctor found {} error(s) and {} warning(s)",
+            errors,
+            warnings
+        ));
+    }
+    if warnings > 0 {
+        println!("{} {} warning(s), no errors", "⚠️".yellow(), warnings);
+    } else {
+        println!("{} No problems found", "✅".green());
+    }
+    Ok(())
+}
+
+async fn apply(fix: Fix, cache_dir: &Path) -> Result<()> {
+    match fix {
+        Fix::AddToPath => shell::modify_path(cache_dir).map(|_| ()),
+        Fix::InstallProxies => proxy::install_proxies(),
+        Fix::UpdateTemplates => {
+            let templates_ref = configured_templates_ref();
+            common::download_templates(
+                &common::templates_root(cache_dir, templates_ref.as_deref())?,
+                &Source::Remote,
+                templates_ref.as_deref(),
+            )
+            .await
+        }
+    }
+}
+
+fn check_config(cache_dir: &Path) -> Finding {
+    let config_path = cache_dir.join("config.toml");
+    let Ok(content) = fs::read_to_string(&config_path) else {
+        return Finding::fail(
+            format!("{} not found", config_path.display()),
+            "Run 'nockup install' to set up nockup",
+        );
+    };
+    match NockupConfig::parse(&content) {
+        Ok(_) => Finding::pass(format!("{} is valid", config_path.display())),
+        Err(e) => Finding::fail(
+            format!(
+                "{} could not be parsed: {}",
+                config_path.display(),
+                e.to_string().trim().replace('\n', "; ")
+            ),
+            "Correct the file by hand, or remove it and run 'nockup install'",
+        ),
+    }
+}
+
+fn check_git() -> Finding {
+    let mirrors = common::Mirrors::load();
+    match which::which("git") {
+        Ok(path) => Finding::pass(format!("git found at {}", path.display())),
+        // Templates are fetched as archives when the source publishes them.
+        Err(_) if mirrors.templates_api.is_some() && mirrors.templates_archive.is_some() => {
+            Finding::warn(
+                "git was not found on PATH",
+                "Templates are downloaded as archives; install git only if that fails",
+            )
+        }
+        Err(_) => Finding::fail(
+            "git was not found on PATH",
+            "Install git, or set mirrors.templates_api and mirrors.templates_archive",
+        ),
+    }
+}
+
+fn check_signing_keys() -> Finding {
+    match pgp::trusted_keyring() {
+        Ok(_) => Finding::pass("Trusted signing keys loaded"),
+        Err(e) => Finding::fail(
+            format!("Trusted signing keys could not be loaded: {}", e),
+            format!(
+                "Check [signing] in config.toml and {}",
+                pgp::TRUSTED_KEYS_ENV
+            ),
+        ),
+    }
+}
+
+fn check_path(cache_dir: &Path) -> Finding {
+    let bin_dir = cache_dir.join("bin");
+    let on_path = std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir == bin_dir));
+    if on_path {
+        return Finding::pass(format!("{} is on PATH", bin_dir.display()));
+    }
+    Finding::warn(
+        format!("{} is not on PATH", bin_dir.display()),
+        format!(
+            "Add '{}' to your shell startup file",
+            shell::posix_source_line(cache_dir)
+        ),
+    )
+    .fixable(Fix::AddToPath)
+}
+
+fn check_proxies(bin_dir: &Path) -> Finding {
+    let missing: Vec<&str> = TOOLCHAIN_BINARIES
+        .into_iter()
+        .filter(|binary| !bin_dir.join(binary).exists())
+        .collect();
+    if missing.is_empty() {
+        return Finding::pass(format!(
+            "{} proxies are installed",
+            TOOLCHAIN_BINARIES.join(" and ")
+        ));
+    }
+    Finding::warn(
+        format!(
+            "{} missing from {}",
+            missing.join(" and "),
+            bin_dir.display()
+        ),
+        "Run 'nockup toolchain default <name>' to reinstall them",
+    )
+    .fixable(Fix::InstallProxies)
+}
+
+/// Check the default toolchain is installed and built for this machine.
+async fn check_toolchain() -> Result<Vec<Finding>> {
+    let Some(name) = toolchain::default_toolchain()? else {
+        return Ok(vec![Finding::fail(
+            "No default toolchain is configured",
+            "Run 'nockup install' or 'nockup toolchain default <name>'",
+        )]);
+    };
+    if !toolchain::is_installed(&name)? {
+        return Ok(vec![Finding::fail(
+            format!("Default toolchain '{}' is not installed", name),
+            "Run 'nockup update', or pick another with 'nockup toolchain default <name>'",
+        )]);
+    }
+
+    let mut findings = vec![Finding::pass(format!(
+        "Default toolchain '{}' is installed",
+        name
+    ))];
+    let bin_dir = toolchain::toolchains_dir()?.join(&name).join("bin");
+    for binary in TOOLCHAIN_BINARIES {
+        let path = bin_dir.join(binary);
+        if !path.exists() {
+            continue;
+        }
+        findings.push(match version::matches_host_architecture(&path).await {
+            Ok(true) => Finding::pass(format!(
+                "{} is built for {}",
+                binary,
+                std::env::consts::ARCH
+            )),
+            Ok(false) => Finding::fail(
+                format!(
+                    "{} in toolchain '{}' is not built for {}",
+                    binary,
+                    name,
+                    std::env::consts::ARCH
+                ),
+                format!(
+                    "Check 'architecture' in config.toml is {}, then run 'nockup update'",
+                    common::get_target_identifier()
+                ),
+            ),
+            Err(e) => Finding::warn(
+                format!("Could not check the architecture of {}: {}", binary, e),
+                "Install 'file' to enable this check",
+            ),
+        });
+    }
+    Ok(findings)
+}
+
+fn configured_templates_ref() -> Option<String> {
+    NockupConfig::load()
+        .ok()
+        .and_then(|config| config.templates_ref)
+}
+
+async fn check_templates(cache_dir: &Path) -> Finding {
+    if let Some(templates_ref) = configured_templates_ref() {
+        let templates_dir = match common::templates_root(cache_dir, Some(&templates_ref)) {
+            Ok(root) => root.join("templates"),
+            Err(e) => {
+                return Finding::fail(
+                    format!("{:#}", e),
+                    "Fix it with 'nockup config set templates_ref <ref>'",
+                )
+            }
+        };
+        return match common::templates_version(&templates_dir) {
+            Some(version) if version.git_ref == templates_ref => Finding::pass(format!(
+                "Project templates are pinned to {} ({})",
+                templates_ref,
+                &version.commit[..version.commit.len().min(7)]
+            )),
+            _ => Finding::warn(
+                format!(
+                    "Project templates {} have not been downloaded",
+                    templates_ref
+                ),
+                "Run 'nockup update' to download them",
+            )
+            .fixable(Fix::UpdateTemplates),
+        };
+    }
+
+    let commit_file = cache_dir.join("templates").join("commit.toml");
+    let local = fs::read_to_string(&commit_file)
+        .ok()
+        .and_then(|content| toml::from_str::<toml::Value>(&content).ok())
+        .and_then(|commit| commit.get("commit")?.get("id")?.as_str().map(String::from));
+    let Some(local) = local else {
+        return Finding::warn(
+            "Project templates have not been downloaded",
+            "Run 'nockup update' to download them",
+        )
+        .fixable(Fix::UpdateTemplates);
+    };
+
+    match common::get_git_commit_id().await {
+        Ok(latest) if latest == local => Finding::pass("Project templates are up to date"),
+        Ok(latest) => Finding::warn(
+            format!(
+                "Project templates are out of date ({} → {})",
+                &local[..local.len().min(7)],
+                &latest[..latest.len().min(7)]
+            ),
+            "Run 'nockup update' to refresh them",
+        )
+        .fixable(Fix::UpdateTemplates),
+        Err(e) => Finding::warn(
+            format!("Could not check for template updates: {}", e),
+            "Check your network connection",
+        ),
+    }
+}
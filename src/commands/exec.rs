@@ -0,0 +1,16 @@
+use std::ffi::OsString;
+
+use anyhow::Result;
+
+use crate::{proxy, toolchain};
+
+pub async fn run(command: Vec<OsString>) -> Result<i32> {
+    let (override_name, mut command) = proxy::split_toolchain_override(command);
+    if command.is_empty() {
+        return Err(anyhow::anyhow!("No command given to run"));
+    }
+    let program = command.remove(0);
+
+    let (name, bin_dir) = toolchain::select_toolchain(override_name.as_deref()).await?;
+    proxy::exec(&program, &command, &name, &bin_dir)
+}
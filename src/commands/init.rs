@@ -6,6 +6,8 @@ use anyhow::{Context, Result};
 use colored::Colorize;
 use handlebars::Handlebars;
 
+use crate::commands::common::{self, Source};
+use crate::config::{self, NockupConfig};
 use crate::lib_manager::{process_libraries, ProjectManifest};
 
 pub async fn run(project_name: String) -> Result<()> {
@@ -19,10 +21,6 @@ pub async fn run(project_name: String) -> Result<()> {
     );
 
     let target_dir = Path::new(project_name);
-    // Use cache dir ~/.nockup/templates/{{manifest.template}}
-    let template_dir = dirs::home_dir()
-        .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
-        .join(format!(".nockup/templates/{}", manifest.project.template));
 
     // Check if target directory already exists
     if target_dir.exists() {
@@ -31,6 +29,19 @@ pub async fn run(project_name: String) -> Result<()> {
         ));
     }
 
+    // Use ~/.nockup/templates/{{manifest.template}}, or the pinned templates
+    // version, downloading it on first use.
+    let templates_ref = NockupConfig::load()
+        .ok()
+        .and_then(|config| config.templates_ref);
+    let templates_root = common::templates_root(&config::nockup_home()?, templates_ref.as_deref())?;
+    let templates_dir = templates_root.join("templates");
+    if templates_ref.is_some() && !templates_dir.exists() {
+        common::download_templates(&templates_root, &Source::Remote, templates_ref.as_deref())
+            .await?;
+    }
+    let template_dir = templates_dir.join(&manifest.project.template);
+
     // Check if template directory exists
     if !template_dir.exists() {
         return Err(anyhow::anyhow!(
@@ -44,6 +55,7 @@ pub async fn run(project_name: String) -> Result<()> {
 
     // Copy template directory to new project location
     copy_template_directory(template_dir.as_path(), target_dir, &context)?;
+    record_templates_version(target_dir, &templates_dir)?;
 
     // Process library dependencies from manifest
     process_libraries(target_dir, &manifest)
@@ -68,7 +80,8 @@ fn load_project_config(project_name: &str) -> Result<ProjectManifest> {
 
     if !config_path.exists() {
         return Err(anyhow::anyhow!(
-            "Project configuration file '{}.toml' not found", project_name
+            "Project configuration file '{}.toml' not found",
+            project_name
         ));
     }
 
@@ -85,6 +98,29 @@ fn load_project_config(project_name: &str) -> Result<ProjectManifest> {
         .with_context(|| format!("Failed to parse {}.toml", project_name))
 }
 
+/// Note in the new project's `manifest.toml` which version of the templates
+/// it was generated from.
+fn record_templates_version(project_dir: &Path, templates_dir: &Path) -> Result<()> {
+    let manifest_path = project_dir.join("manifest.toml");
+    let Some(version) = common::templates_version(templates_dir) else {
+        return Ok(());
+    };
+    if !manifest_path.exists() {
+        return Ok(());
+    }
+
+    let mut manifest: toml_edit::DocumentMut = fs::read_to_string(&manifest_path)?
+        .parse()
+        .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+    let mut template = toml_edit::Table::new();
+    template["ref"] = toml_edit::value(version.git_ref);
+    template["commit"] = toml_edit::value(version.commit);
+    manifest["template"] = toml_edit::Item::Table(template);
+    fs::write(&manifest_path, manifest.to_string())
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    Ok(())
+}
+
 fn create_template_context(manifest: &ProjectManifest) -> Result<HashMap<String, String>> {
     let mut context = HashMap::new();
 
@@ -1,14 +1,27 @@
 use std::fs;
-use std::io::Read;
 use std::path::PathBuf;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use colored::Colorize;
 
-use super::common;
-
-pub async fn run() -> Result<()> {
-    let cache_dir = common::get_cache_dir()?;
+use super::common::{self, Source};
+use crate::config::{self, NockupConfig};
+use crate::shell;
+
+pub async fn run(
+    from: Option<PathBuf>,
+    profile: Option<String>,
+    templates_ref: Option<String>,
+    modify_path: bool,
+) -> Result<()> {
+    let source = Source::from_option(from)?;
+    let cache_dir = config::nockup_home()?;
+    let mut config = NockupConfig::load_or_default()?;
+    if let Some(templates_ref) = templates_ref {
+        config::setting("templates_ref")?.parse(std::slice::from_ref(&templates_ref))?;
+        config.templates_ref = Some(templates_ref);
+    }
+    let templates_root = common::templates_root(&cache_dir, config.templates_ref.as_deref())?;
 
     println!("{} Setting up nockup cache directory...", "🚀".green());
     println!(
@@ -21,33 +34,56 @@ pub async fn run() -> Result<()> {
     create_cache_structure(&cache_dir).await?;
 
     // Download or update templates
-    common::download_templates(&cache_dir).await?;
+    common::download_templates(&templates_root, &source, config.templates_ref.as_deref()).await?;
 
     // Download toolchain files
-    common::download_toolchain_files(&cache_dir).await?;
+    common::download_toolchain_files(&cache_dir, &source).await?;
 
     // Set default channel to stable and this architecture
-    let config_path = cache_dir.join("config.toml");
-    let mut config = common::get_or_create_config()?;
-    println!("📝 Config installed at: {}", config_path.display());
-    config["channel"] = toml::Value::String("stable".into());
-    config["architecture"] = toml::Value::String(common::get_target_identifier());
-    fs::write(config_path, toml::to_string(&config)?).context("Failed to write config file")?;
-
-    // Write commit details to status file
-    common::write_commit_details(&cache_dir).await?;
+    println!(
+        "📝 Config installed at: {}",
+        config::config_path()?.display()
+    );
+    config.channel = "stable".into();
+    config.architecture = common::get_target_identifier();
+    if let Some(profile) = profile {
+        // Reject unknown profiles before they are saved.
+        common::read_channel_manifest(
+            &cache_dir
+                .join("toolchains")
+                .join("channel-nockup-stable.toml"),
+        )?
+        .profile_packages(&profile)?;
+        config.profile = Some(profile);
+    }
+    config.save()?;
 
     // Download binaries for current channel
-    common::download_binaries(&config).await?;
+    common::download_binaries(&config, &source).await?;
 
-    // Prepend cache bin directory to PATH
-    prepend_path_to_shell_rc(&cache_dir.join("bin")).await?;
+    // Put the cache bin directory on PATH
+    if modify_path {
+        for rc_path in shell::modify_path(&cache_dir)? {
+            println!("{} Updated {}", "📝".green(), rc_path.display());
+        }
+    } else {
+        shell::write_env_scripts(&cache_dir)?;
+    }
 
     println!("{} Setup complete!", "✅".green());
     println!(
         "{} Templates are now available in: {}",
         "📂".blue(),
-        cache_dir.join("templates").display().to_string().cyan()
+        templates_root
+            .join("templates")
+            .display()
+            .to_string()
+            .cyan()
+    );
+    println!(
+        "{} To put nockup on PATH in this shell, run: {}",
+        "💡".blue(),
+        shell::posix_source_line(&cache_dir).cyan()
     );
 
     Ok(())
@@ -67,30 +103,3 @@ async fn create_cache_structure(cache_dir: &PathBuf) -> Result<()> {
     println!("{} Created directory structure", "✓".green());
     Ok(())
 }
-
-async fn prepend_path_to_shell_rc(bin_dir: &PathBuf) -> Result<()> {
-    let shell = std::env::var("SHELL").unwrap_or_default();
-    let rc_file = if shell.contains("zsh") {
-        dirs::home_dir().unwrap().join(".zshrc")
-    } else if shell.contains("bash") {
-        dirs::home_dir().unwrap().join(".bashrc")
-    } else {
-        return Ok(());
-    };
-
-    let mut contents = String::new();
-    if rc_file.exists() {
-        let mut file = fs::File::open(&rc_file)?;
-        file.read_to_string(&mut contents)?;
-    }
-
-    let path_entry = format!("export PATH=\"{}:$PATH\"", bin_dir.display());
-    println!("{}", path_entry);
-    if !contents.contains(&path_entry) {
-        let new_contents = format!("{}\n{}", contents, path_entry);
-        fs::write(&rc_file, new_contents)?;
-        println!("{} Updated {}", "📝".green(), rc_file.display());
-    }
-
-    Ok(())
-}
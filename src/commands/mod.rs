@@ -1,7 +1,18 @@
 pub mod build;
+pub mod bundle;
 pub mod channel;
 pub mod common;
+pub mod completions;
+pub mod config;
+pub mod doctor;
+pub mod exec;
 pub mod init;
 pub mod install;
+pub mod rollback;
 pub mod run;
+pub mod self_update;
+pub mod show;
+pub mod target;
+pub mod toolchain;
 pub mod update;
+pub mod verify;
@@ -0,0 +1,17 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::generation;
+
+pub async fn run() -> Result<()> {
+    let generation = generation::rollback()?;
+    match generation.toolchain {
+        Some(name) => println!(
+            "{} Rolled back to the previous nockup binary and toolchain '{}'",
+            "⏪".green(),
+            name.cyan()
+        ),
+        None => println!("{} Rolled back to the previous nockup binary", "⏪".green()),
+    }
+    Ok(())
+}
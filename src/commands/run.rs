@@ -5,6 +5,8 @@ use anyhow::{Context, Result};
 use colored::Colorize;
 use tokio::process::Command;
 
+use crate::toolchain;
+
 pub async fn run(project: String, args: Vec<String>) -> Result<()> {
     let project_dir = Path::new(&project);
 
@@ -17,7 +19,8 @@ pub async fn run(project: String, args: Vec<String>) -> Result<()> {
     let manifest_path = project_dir.join("manifest.toml");
     if !manifest_path.exists() {
         return Err(anyhow::anyhow!(
-            "Not a NockApp project: '{}' missing manifest.toml", project
+            "Not a NockApp project: '{}' missing manifest.toml",
+            project
         ));
     }
 
@@ -27,6 +30,9 @@ pub async fn run(project: String, args: Vec<String>) -> Result<()> {
         return Err(anyhow::anyhow!("No Cargo.toml found in '{}'", project));
     }
 
+    // Resolve the toolchain pinned by the project, if any
+    let toolchain_bin = toolchain::resolve_project_toolchain(project_dir).await?;
+
     println!("{} Running project '{}'...", "🔨".green(), project.cyan());
 
     // Run cargo run in the project directory
@@ -37,6 +43,9 @@ pub async fn run(project: String, args: Vec<String>) -> Result<()> {
         .current_dir(project_dir)
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit());
+    if let Some(bin_dir) = &toolchain_bin {
+        toolchain::prepend_to_path(&mut command, bin_dir)?;
+    }
 
     // Add separator and pass through additional arguments to the program
     if !args.is_empty() {
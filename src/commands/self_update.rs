@@ -0,0 +1,121 @@
+use std::fs;
+use std::io::{BufRead, Write};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use super::common::{self, Source};
+use crate::cli::SelfAction;
+use crate::config::{self, NockupConfig};
+use crate::receipt::Receipt;
+use crate::{generation, proxy, shell};
+
+pub async fn run(action: SelfAction) -> Result<()> {
+    match action {
+        SelfAction::Update => update().await,
+        SelfAction::Uninstall { yes } => uninstall(yes),
+    }
+}
+
+/// Replace the running nockup executable with the latest build from the
+/// current channel.
+async fn update() -> Result<()> {
+    let config = NockupConfig::load()?;
+    let channel = config.channel.as_str();
+    let architecture = config.architecture.as_str();
+    let exe = std::env::current_exe()
+        .and_then(|exe| exe.canonicalize())
+        .context("Failed to locate the nockup executable")?;
+
+    let cache_dir = config::nockup_home()?;
+    common::download_toolchain_files(&cache_dir, &Source::Remote).await?;
+    let manifest = common::read_channel_manifest(
+        &cache_dir
+            .join("toolchains")
+            .join(format!("channel-nockup-{}.toml", channel)),
+    )?;
+
+    if !manifest.target("nockup", architecture)?.available {
+        return Err(anyhow::anyhow!(
+            "nockup is not available for {} on channel '{}'",
+            architecture,
+            channel
+        ));
+    }
+
+    let installed_nockup = cache_dir.join("bin").join("nockup").canonicalize().ok();
+    let staging_dir = generation::staging_dir()?;
+    let result = async {
+        let staged_bin_dir = staging_dir.join("bin");
+        common::install_components(
+            &manifest,
+            architecture,
+            &["nockup"],
+            &staged_bin_dir,
+            &Source::Remote,
+        )
+        .await?;
+        common::replace_executable(&staged_bin_dir.join("nockup"), &exe)?;
+        // Only the nockup in ~/.nockup/bin has a receipt, and only it backs
+        // the proxies, which still link to the binary it replaced.
+        if installed_nockup.as_ref() == Some(&exe) {
+            Receipt::copy_component(&staging_dir, &cache_dir, "nockup")?;
+            proxy::install_proxies()?;
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    let _ = fs::remove_dir_all(&staging_dir);
+    result?;
+
+    println!(
+        "{} Updated nockup at {}",
+        "✅".green(),
+        exe.display().to_string().cyan()
+    );
+    Ok(())
+}
+
+/// Remove the nockup cache, the PATH lines added to shell startup files, and
+/// the running nockup executable.
+fn uninstall(yes: bool) -> Result<()> {
+    let cache_dir = config::nockup_home()?;
+    let exe = std::env::current_exe()
+        .and_then(|exe| exe.canonicalize())
+        .context("Failed to locate the nockup executable")?;
+
+    if !yes {
+        println!("This will remove:");
+        println!("  {}", cache_dir.display());
+        println!("  {}", exe.display());
+        println!("  nockup's PATH entries in your shell startup files");
+        print!("Continue? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Err(anyhow::anyhow!("Uninstall cancelled"));
+        }
+    }
+
+    for rc_path in shell::restore_path(&cache_dir)? {
+        println!("{} Updated {}", "📝".green(), rc_path.display());
+    }
+
+    if cache_dir.exists() {
+        if exe.starts_with(&cache_dir) {
+            self_replace::self_delete_outside_path(&cache_dir)
+                .context("Failed to remove the nockup executable")?;
+        }
+        fs::remove_dir_all(&cache_dir)
+            .with_context(|| format!("Failed to remove {}", cache_dir.display()))?;
+        println!("{} Removed {}", "🗑️".green(), cache_dir.display());
+    }
+    if !exe.starts_with(&cache_dir) {
+        self_replace::self_delete().context("Failed to remove the nockup executable")?;
+        println!("{} Removed {}", "🗑️".green(), exe.display());
+    }
+
+    println!("{} nockup has been uninstalled", "✅".green());
+    Ok(())
+}
@@ -0,0 +1,400 @@
+//! `nockup show`: a report of everything nockup has installed.
+//!
+//! The report is assembled from the records kept alongside each install:
+//! receipts for binaries and cached libraries, `commit.toml` for templates,
+//! and the channel manifests themselves.  Each item is listed with the disk
+//! space it takes up.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Serialize;
+
+use super::common::{self, TemplatesVersion};
+use super::target;
+use crate::config::{self, NockupConfig};
+use crate::receipt::{ComponentReceipt, LibraryReceipt, LibraryReceipts, Receipt};
+use crate::toolchain;
+
+#[derive(Serialize)]
+struct Report {
+    home: PathBuf,
+    version: &'static str,
+    channel: String,
+    architecture: String,
+    profile: String,
+    default_toolchain: Option<String>,
+    templates_ref: Option<String>,
+    /// The `nockup` binary in `~/.nockup/bin`, if it was installed there.
+    nockup: Option<ComponentReceipt>,
+    toolchains: Vec<Install>,
+    targets: Vec<Install>,
+    templates: Vec<Templates>,
+    manifests: Vec<Manifest>,
+    libraries: Vec<Library>,
+    /// Downloads and API responses kept between runs.
+    caches: Vec<Cache>,
+    /// Disk usage of the whole nockup home directory, in bytes.
+    size: u64,
+}
+
+/// A toolchain, or a toolchain's binaries for another target.
+#[derive(Serialize)]
+struct Install {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    path: PathBuf,
+    /// Empty if the install predates receipts.
+    components: BTreeMap<String, ComponentReceipt>,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct Templates {
+    path: PathBuf,
+    /// Whether `nockup init` uses these templates.
+    active: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    version: Option<TemplatesVersion>,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    channel: String,
+    path: PathBuf,
+    commit: Option<String>,
+    date: Option<String>,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct Library {
+    name: String,
+    path: PathBuf,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    receipt: Option<LibraryReceipt>,
+    size: u64,
+}
+
+#[derive(Serialize)]
+struct Cache {
+    name: &'static str,
+    path: PathBuf,
+    size: u64,
+}
+
+pub fn run(json: bool) -> Result<()> {
+    let report = report()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    Ok(())
+}
+
+fn report() -> Result<Report> {
+    let home = config::nockup_home()?;
+    let config = NockupConfig::load_or_default()?;
+
+    let toolchains = toolchain::installed_toolchains()?
+        .into_iter()
+        .map(|name| {
+            let path = toolchain::toolchains_dir()?.join(&name);
+            install(name, None, path)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut targets = Vec::new();
+    for triple in target::installed_targets()? {
+        for entry in fs::read_dir(target::targets_dir()?.join(&triple))? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            targets.push(install(name, Some(triple.clone()), path)?);
+        }
+    }
+
+    let active_templates = common::templates_dir()?;
+    let mut template_roots = vec![home.clone()];
+    if let Ok(entries) = fs::read_dir(home.join("template-refs")) {
+        let mut refs: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        refs.sort();
+        template_roots.extend(refs);
+    }
+    let templates = template_roots
+        .into_iter()
+        .filter(|root| root.join("templates").is_dir())
+        .map(|root| {
+            let templates_dir = root.join("templates");
+            Templates {
+                active: templates_dir == active_templates,
+                version: common::templates_version(&templates_dir),
+                size: dir_size(&templates_dir) + dir_size(&root.join("manifests")),
+                path: templates_dir,
+            }
+        })
+        .collect();
+
+    let mut manifests = Vec::new();
+    if let Ok(entries) = fs::read_dir(toolchain::toolchains_dir()?) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let Some(channel) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("channel-nockup-"))
+                .and_then(|name| name.strip_suffix(".toml"))
+            else {
+                continue;
+            };
+            let manifest = common::read_channel_manifest(&path).ok();
+            manifests.push(Manifest {
+                channel: channel.to_string(),
+                commit: manifest
+                    .as_ref()
+                    .and_then(|manifest| manifest.commit.clone()),
+                date: manifest.and_then(|manifest| manifest.date),
+                size: dir_size(&path),
+                path,
+            });
+        }
+    }
+    manifests.sort_by(|a, b| a.channel.cmp(&b.channel));
+
+    let library_cache = home.join("library_cache");
+    let mut receipts = LibraryReceipts::load(&library_cache)?;
+    let mut libraries = Vec::new();
+    if let Ok(entries) = fs::read_dir(&library_cache) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if !path.is_dir() {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            libraries.push(Library {
+                receipt: receipts.libraries.remove(&name),
+                size: dir_size(&path),
+                name,
+                path,
+            });
+        }
+    }
+    libraries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let caches = [
+        ("downloads", home.join("downloads")),
+        ("api", home.join("cache").join("api")),
+    ]
+    .into_iter()
+    .filter(|(_, path)| path.exists())
+    .map(|(name, path)| Cache {
+        name,
+        size: dir_size(&path),
+        path,
+    })
+    .collect();
+
+    Ok(Report {
+        version: env!("FULL_VERSION"),
+        profile: config.profile().to_string(),
+        channel: config.channel,
+        architecture: config.architecture,
+        default_toolchain: config.toolchain,
+        templates_ref: config.templates_ref,
+        nockup: Receipt::load(&home)?.and_then(|mut receipt| receipt.components.remove("nockup")),
+        toolchains,
+        targets,
+        templates,
+        manifests,
+        libraries,
+        caches,
+        size: dir_size(&home),
+        home,
+    })
+}
+
+fn install(name: String, target: Option<String>, path: PathBuf) -> Result<Install> {
+    Ok(Install {
+        name,
+        target,
+        components: Receipt::load(&path)?
+            .map(|receipt| receipt.components)
+            .unwrap_or_default(),
+        size: dir_size(&path),
+        path,
+    })
+}
+
+fn print_report(report: &Report) {
+    println!(
+        "{} {}",
+        "nockup".bold(),
+        format!("{} ({})", report.version, format_size(report.size)).dimmed()
+    );
+    println!("  home          {}", report.home.display());
+    println!("  channel       {}", report.channel);
+    println!("  architecture  {}", report.architecture);
+    println!("  profile       {}", report.profile);
+    if let Some(nockup) = &report.nockup {
+        println!("  binary        {}", describe(nockup));
+    }
+
+    section("Toolchains", report.toolchains.is_empty());
+    for install in &report.toolchains {
+        let default = report.default_toolchain.as_deref() == Some(install.name.as_str());
+        let name = if default {
+            format!("{} {}", install.name, "(default)".green())
+        } else {
+            install.name.clone()
+        };
+        print_install(&name, install);
+    }
+
+    section("Targets", report.targets.is_empty());
+    for install in &report.targets {
+        let name = format!(
+            "{} ({})",
+            install.target.as_deref().unwrap_or_default(),
+            install.name
+        );
+        print_install(&name, install);
+    }
+
+    section("Templates", report.templates.is_empty());
+    for templates in &report.templates {
+        let version = match &templates.version {
+            Some(version) => format!(
+                "{} ({})",
+                version.git_ref,
+                &version.commit[..version.commit.len().min(7)]
+            ),
+            None => "unknown version".to_string(),
+        };
+        let active = if templates.active {
+            format!(" {}", "(active)".green())
+        } else {
+            String::new()
+        };
+        println!(
+            "  {}{}  {}  {}",
+            version,
+            active,
+            format_size(templates.size).dimmed(),
+            templates.path.display().to_string().dimmed()
+        );
+    }
+
+    section("Channel manifests", report.manifests.is_empty());
+    for manifest in &report.manifests {
+        let build = match (&manifest.date, &manifest.commit) {
+            (Some(date), Some(commit)) => format!("{} ({})", date, &commit[..commit.len().min(7)]),
+            (None, Some(commit)) => commit[..commit.len().min(7)].to_string(),
+            _ => "no build recorded".to_string(),
+        };
+        println!(
+            "  {}  {}  {}",
+            manifest.channel,
+            build,
+            format_size(manifest.size).dimmed()
+        );
+    }
+
+    section("Library cache", report.libraries.is_empty());
+    for library in &report.libraries {
+        let source = match &library.receipt {
+            Some(receipt) => format!(
+                "{} ({})",
+                receipt.url,
+                &receipt.commit[..receipt.commit.len().min(7)]
+            ),
+            None => "no receipt".to_string(),
+        };
+        println!(
+            "  {}  {}  {}",
+            library.name,
+            source,
+            format_size(library.size).dimmed()
+        );
+    }
+
+    section("Caches", report.caches.is_empty());
+    for cache in &report.caches {
+        println!(
+            "  {}  {}  {}",
+            cache.name,
+            format_size(cache.size).dimmed(),
+            cache.path.display().to_string().dimmed()
+        );
+    }
+}
+
+fn section(title: &str, empty: bool) {
+    println!();
+    println!("{}", title.bold());
+    if empty {
+        println!("  {}", "none".dimmed());
+    }
+}
+
+fn print_install(name: &str, install: &Install) {
+    println!("  {}  {}", name, format_size(install.size).dimmed());
+    if install.components.is_empty() {
+        println!("    {}", "no receipt".dimmed());
+    }
+    for (component, receipt) in &install.components {
+        println!("    {}  {}", component, describe(receipt));
+    }
+}
+
+/// One-line summary of a receipt, e.g. `0.1.0, blake3 1a2b3c4d, installed
+/// 2025-09-01T12:00:00Z`.
+fn describe(receipt: &ComponentReceipt) -> String {
+    let mut parts = Vec::new();
+    if let Some(version) = &receipt.version {
+        parts.push(version.clone());
+    }
+    parts.push(format!(
+        "blake3 {}",
+        &receipt.blake3[..receipt.blake3.len().min(8)]
+    ));
+    parts.push(format!("installed {}", receipt.installed));
+    parts.join(", ").dimmed().to_string()
+}
+
+/// Total size of the files under `path`, not following symlinks.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
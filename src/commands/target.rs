@@ -0,0 +1,226 @@
+//! `nockup target`: binaries of the default toolchain built for other
+//! machines.
+//!
+//! Each additional target lives in `~/.nockup/targets/<triple>/<toolchain>`,
+//! apart from the toolchains the proxies run, so adding one never changes
+//! what `hoon` and `hoonc` resolve to on this machine.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use super::common::{self, Source};
+use crate::cli::TargetAction;
+use crate::config::{self, NockupConfig};
+use crate::generation;
+use crate::manifest::ChannelManifest;
+use crate::receipt::RECEIPT_FILE;
+use crate::toolchain;
+
+pub async fn run(action: TargetAction) -> Result<()> {
+    match action {
+        TargetAction::Add { targets } => add(&targets).await,
+        TargetAction::Remove { targets } => remove(&targets),
+        TargetAction::List => list(),
+    }
+}
+
+pub fn targets_dir() -> Result<PathBuf> {
+    Ok(config::nockup_home()?.join("targets"))
+}
+
+/// Target triples with binaries for some toolchain, sorted.
+pub fn installed_targets() -> Result<Vec<String>> {
+    let targets_dir = targets_dir()?;
+    let mut targets = Vec::new();
+    if !targets_dir.exists() {
+        return Ok(targets);
+    }
+    for entry in fs::read_dir(&targets_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            targets.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    targets.sort();
+    Ok(targets)
+}
+
+/// The default toolchain and the channel manifest it was installed from.
+fn default_toolchain() -> Result<(String, ChannelManifest)> {
+    let name = toolchain::default_toolchain()?.ok_or_else(|| {
+        anyhow::anyhow!("No default toolchain configured. Run 'nockup install' first.")
+    })?;
+    let manifest_path = toolchain::toolchains_dir()?
+        .join(&name)
+        .join("manifest.toml");
+    let manifest = common::read_channel_manifest(&manifest_path).with_context(|| {
+        format!(
+            "Toolchain '{}' has no channel manifest; reinstall it with 'nockup toolchain install'",
+            name
+        )
+    })?;
+    Ok((name, manifest))
+}
+
+/// Packages of the configured profile that make up a toolchain.
+fn toolchain_packages<'a>(
+    manifest: &'a ChannelManifest,
+    config: &NockupConfig,
+) -> Result<Vec<&'a str>> {
+    Ok(manifest
+        .profile_packages(config.profile())?
+        .into_iter()
+        .filter(|&package| package != "nockup")
+        .collect())
+}
+
+/// Targets the manifest has a build of any of `packages` for.
+fn available_targets(manifest: &ChannelManifest, packages: &[&str]) -> BTreeSet<String> {
+    packages
+        .iter()
+        .filter_map(|&package| manifest.pkg.get(package))
+        .flat_map(|package| package.target.keys().cloned())
+        .collect()
+}
+
+async fn add(targets: &[String]) -> Result<()> {
+    let config = NockupConfig::load_or_default()?;
+    let (name, manifest) = default_toolchain()?;
+    let packages = toolchain_packages(&manifest, &config)?;
+    let available = available_targets(&manifest, &packages);
+
+    // Check every target before downloading anything.
+    for target in targets {
+        if !available.contains(target) {
+            return Err(anyhow::anyhow!(
+                "Toolchain '{}' has no builds for '{}' (available: {})",
+                name,
+                target,
+                available.iter().cloned().collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+
+    for target in targets {
+        if *target == config.architecture {
+            println!(
+                "{} {} is this machine's target; its binaries are in toolchain '{}'",
+                "💡".blue(),
+                target.cyan(),
+                name.cyan()
+            );
+            continue;
+        }
+        let target_dir = targets_dir()?.join(target).join(&name);
+        if target_dir.join("bin").is_dir() {
+            println!(
+                "{} Target {} is already installed for toolchain '{}'",
+                "✅".green(),
+                target.cyan(),
+                name.cyan()
+            );
+            continue;
+        }
+
+        println!(
+            "{} Adding target {} for toolchain '{}'...",
+            "⬇️".green(),
+            target.cyan(),
+            name.cyan()
+        );
+        // Download and verify into staging so a failure leaves nothing behind.
+        let staging_dir = generation::staging_dir()?;
+        let result = async {
+            let staged_bin = staging_dir.join("bin");
+            common::install_components(&manifest, target, &packages, &staged_bin, &Source::Remote)
+                .await?;
+            fs::create_dir_all(&target_dir)?;
+            fs::rename(&staged_bin, target_dir.join("bin"))
+                .with_context(|| format!("Failed to install target '{}'", target))?;
+            fs::rename(
+                staging_dir.join(RECEIPT_FILE),
+                target_dir.join(RECEIPT_FILE),
+            )
+            .context("Failed to record install receipt")
+        }
+        .await;
+        let _ = fs::remove_dir_all(&staging_dir);
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&target_dir);
+        }
+        result?;
+
+        // Binaries for older toolchains are superseded.
+        for entry in fs::read_dir(targets_dir()?.join(target))? {
+            let path = entry?.path();
+            if path != target_dir {
+                fs::remove_dir_all(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+
+        println!(
+            "{} Added target {} in {}",
+            "✅".green(),
+            target.cyan(),
+            target_dir.join("bin").display()
+        );
+    }
+    Ok(())
+}
+
+fn remove(targets: &[String]) -> Result<()> {
+    let installed = installed_targets()?;
+    for target in targets {
+        if !installed.contains(target) {
+            return Err(anyhow::anyhow!("Target '{}' is not installed", target));
+        }
+    }
+    for target in targets {
+        fs::remove_dir_all(targets_dir()?.join(target))?;
+        println!("{} Removed target {}", "🗑️".green(), target.cyan());
+    }
+    Ok(())
+}
+
+fn list() -> Result<()> {
+    let config = NockupConfig::load_or_default()?;
+    let installed = installed_targets()?;
+    let (name, mut targets) = match default_toolchain() {
+        Ok((name, manifest)) => {
+            let packages = toolchain_packages(&manifest, &config)?;
+            (Some(name), available_targets(&manifest, &packages))
+        }
+        Err(_) => (None, BTreeSet::new()),
+    };
+    targets.extend(installed.iter().cloned());
+    targets.insert(config.architecture.clone());
+
+    for target in targets {
+        if target == config.architecture {
+            println!("{} {}", target, "(host)".green());
+            continue;
+        }
+        if !installed.contains(&target) {
+            println!("{}", target);
+            continue;
+        }
+        let current = name.as_ref().is_some_and(|name| {
+            targets_dir().is_ok_and(|dir| dir.join(&target).join(name).is_dir())
+        });
+        if current {
+            println!("{} {}", target, "(installed)".green());
+        } else {
+            println!(
+                "{} {}",
+                target,
+                "(installed for another toolchain; run 'nockup target add' to update)".yellow()
+            );
+        }
+    }
+    Ok(())
+}
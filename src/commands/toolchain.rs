@@ -0,0 +1,93 @@
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::channel::ChannelSpec;
+use crate::cli::ToolchainAction;
+use crate::commands::common::Source;
+use crate::toolchain::{self, ToolchainSpec};
+
+pub async fn run(action: ToolchainAction) -> Result<()> {
+    match action {
+        ToolchainAction::List => list_toolchains(),
+        ToolchainAction::Install { channel, commit } => install(channel, commit).await,
+        ToolchainAction::Uninstall { toolchain } => uninstall(&toolchain),
+        ToolchainAction::Default { toolchain } => set_default(&toolchain),
+    }
+}
+
+fn list_toolchains() -> Result<()> {
+    let installed = toolchain::installed_toolchains()?;
+    if installed.is_empty() {
+        println!("No toolchains installed. Run 'nockup install' first.");
+        return Ok(());
+    }
+
+    let default = toolchain::default_toolchain()?;
+    for name in installed {
+        if default.as_deref() == Some(name.as_str()) {
+            println!("{} {}", name, "(default)".green());
+        } else {
+            println!("{}", name);
+        }
+    }
+    Ok(())
+}
+
+async fn install(channel: String, commit: Option<String>) -> Result<()> {
+    let channel_spec = ChannelSpec::parse(&channel)?;
+    if let Some(commit) = &commit {
+        toolchain::validate_commit(commit)?;
+        if channel_spec.pin.is_some() {
+            return Err(anyhow::anyhow!(
+                "'{}' already pins a build; pass either it or --commit",
+                channel
+            ));
+        }
+    }
+
+    let spec = ToolchainSpec {
+        channel: Some(channel),
+        commit,
+    };
+    let (toolchain, manifest) = toolchain::resolve_spec(&spec).await?;
+    if toolchain.is_installed()? {
+        println!(
+            "{} Toolchain '{}' is already installed",
+            "✅".green(),
+            toolchain.name().cyan()
+        );
+        return Ok(());
+    }
+    toolchain::install_toolchain(&toolchain, manifest, &Source::Remote).await?;
+
+    if toolchain::default_toolchain()?.is_none() {
+        toolchain::set_default_toolchain(&toolchain.name())?;
+        println!("Set default toolchain to '{}'.", toolchain.name().cyan());
+    }
+    Ok(())
+}
+
+fn uninstall(name: &str) -> Result<()> {
+    if !toolchain::installed_toolchains()?.contains(&name.to_string()) {
+        return Err(anyhow::anyhow!("Toolchain '{}' is not installed", name));
+    }
+    if toolchain::default_toolchain()?.as_deref() == Some(name) {
+        return Err(anyhow::anyhow!(
+            "Toolchain '{}' is the default toolchain; set another default before uninstalling it",
+            name
+        ));
+    }
+
+    std::fs::remove_dir_all(toolchain::toolchains_dir()?.join(name))?;
+    println!("{} Uninstalled toolchain '{}'", "🗑️".green(), name.cyan());
+    Ok(())
+}
+
+fn set_default(name: &str) -> Result<()> {
+    if !toolchain::installed_toolchains()?.contains(&name.to_string()) {
+        return Err(anyhow::anyhow!("Toolchain '{}' is not installed", name));
+    }
+    toolchain::set_default_toolchain(name)?;
+    println!("Set default toolchain to '{}'.", name.cyan());
+    Ok(())
+}
@@ -1,10 +1,21 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
 use colored::Colorize;
 
-use super::common;
+use super::common::{self, Source};
+use crate::channel::{self, ChannelSpec};
+use crate::config::{self, NockupConfig};
+use crate::manifest::ChannelManifest;
+use crate::status::{self, Status};
+use crate::toolchain;
+
+/// Exit code of `nockup update --check` when updates are available.
+pub const UPDATES_AVAILABLE: i32 = 100;
 
-pub async fn run() -> Result<()> {
-    let cache_dir = common::get_cache_dir()?;
+pub async fn run(from: Option<PathBuf>) -> Result<()> {
+    let source = Source::from_option(from)?;
+    let cache_dir = config::nockup_home()?;
 
     println!("{} Setting up nockup cache directory...", "🚀".green());
     println!(
@@ -13,22 +24,126 @@ pub async fn run() -> Result<()> {
         cache_dir.display().to_string().cyan()
     );
 
-    // Download or update templates
-    common::download_templates(&cache_dir).await?;
+    // Get existing config
+    let config = NockupConfig::load()?;
 
-    // Download toolchain files
-    common::download_toolchain_files(&cache_dir).await?;
+    // Skip the downloads entirely when the installed build is current
+    if pending_changes(&config, &source).await?.is_empty() {
+        println!("{} Everything is up to date", "✅".green());
+        return Ok(());
+    }
 
-    // Write commit details to status file
-    common::write_commit_details(&cache_dir).await?;
+    // Download or update templates
+    let templates_ref = config.templates_ref.as_deref();
+    common::download_templates(
+        &common::templates_root(&cache_dir, templates_ref)?,
+        &source,
+        templates_ref,
+    )
+    .await?;
 
-    // Get existing config
-    let config = common::get_config()?;
+    // Download toolchain files
+    common::download_toolchain_files(&cache_dir, &source).await?;
 
     // Download binaries for current channel
-    common::download_binaries(&config).await?;
+    common::download_binaries(&config, &source).await?;
 
     println!("{} Update complete!", "✅".green());
 
     Ok(())
 }
+
+/// Compare the installed build recorded in `status.toml` with the latest
+/// build of the configured channel and print what an update would change.
+/// Returns the exit code: 0 if up to date, `UPDATES_AVAILABLE` otherwise.
+pub async fn check(from: Option<PathBuf>) -> Result<i32> {
+    let source = Source::from_option(from)?;
+    let config = NockupConfig::load()?;
+    let changes = pending_changes(&config, &source).await?;
+
+    if changes.is_empty() {
+        println!("{} Everything is up to date", "✅".green());
+        return Ok(0);
+    }
+    for (component, old, new) in &changes {
+        println!("  {}: {} → {}", component.cyan(), old, new.green());
+    }
+    println!(
+        "{} {} update(s) available; run 'nockup update' to install them",
+        "⬆️".yellow(),
+        changes.len()
+    );
+    Ok(UPDATES_AVAILABLE)
+}
+
+/// Compare the installed build recorded in `status.toml` with the latest
+/// build of the configured channel.  Returns each component an update would
+/// change, with its installed and latest versions.
+async fn pending_changes(
+    config: &NockupConfig,
+    source: &Source,
+) -> Result<Vec<(String, String, String)>> {
+    let channel = config.channel.as_str();
+    let architecture = config.architecture.as_str();
+    let profile = config.profile();
+
+    println!(
+        "{} Checking channel '{}' for updates...",
+        "🔍".yellow(),
+        channel.cyan()
+    );
+    let manifest = match source {
+        Source::Remote => {
+            let spec = ChannelSpec::parse(channel)?;
+            let commit = channel::resolve_commit(&spec, None).await?;
+            ChannelManifest::parse(&common::fetch_channel_manifest(&spec.channel, &commit).await?)?
+        }
+        Source::Bundle(bundle_dir) => common::read_channel_manifest(
+            &bundle_dir
+                .join("toolchains")
+                .join(format!("channel-nockup-{}.toml", channel)),
+        )?,
+    };
+
+    let status = Status::load()?;
+    let latest = toolchain::toolchain_for_manifest(channel, &manifest)?;
+    let mut changes = Vec::new();
+    if status.toolchain.as_deref() != Some(latest.name().as_str()) {
+        changes.push((
+            "toolchain".to_string(),
+            status
+                .toolchain
+                .clone()
+                .unwrap_or_else(|| "(not installed)".to_string()),
+            latest.name(),
+        ));
+    }
+    for package in manifest.profile_packages(profile)? {
+        let target = manifest.target(package, architecture)?;
+        if !target.available {
+            continue;
+        }
+        let new = status::label(
+            manifest.pkg[package].version.as_deref(),
+            &target.hash_blake3,
+        );
+        let old = match status.components.get(package) {
+            Some(installed) if installed.matches(target) => continue,
+            Some(installed) if !installed.is_intact() => {
+                format!("{} (modified)", installed.label())
+            }
+            Some(installed) => installed.label(),
+            None => "(not installed)".to_string(),
+        };
+        changes.push((package.to_string(), old, new));
+    }
+
+    let templates_ref = config.templates_ref.as_deref();
+    let templates_root = common::templates_root(&config::nockup_home()?, templates_ref)?;
+    if let Some((old, new)) =
+        common::pending_templates(&templates_root, source, templates_ref).await?
+    {
+        changes.push(("templates".to_string(), old, new));
+    }
+    Ok(changes)
+}
@@ -0,0 +1,237 @@
+//! `nockup verify`: re-checking installed binaries.
+//!
+//! Every binary recorded in an install receipt is hashed again and compared
+//! with the receipt, and the receipt itself is compared with the channel
+//! manifest the binary was installed from, so both a modified binary and an
+//! altered receipt are caught.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use colored::Colorize;
+
+use super::common;
+use super::target;
+use crate::config;
+use crate::download::hash_file;
+use crate::manifest::ChannelManifest;
+use crate::receipt::{ComponentReceipt, Receipt};
+use crate::toolchain;
+
+/// A directory of installed binaries with its receipt beside `bin`.
+struct Install {
+    /// How the install is described in reports, e.g. `toolchain stable-336f744`.
+    label: String,
+    dir: PathBuf,
+    /// Whether everything in `bin` should be in the receipt.  `~/.nockup/bin`
+    /// also holds the `hoon` and `hoonc` proxies, which are not.
+    exclusive: bool,
+}
+
+/// Channel manifests of the installed toolchains, by commit.
+struct Manifests(Vec<ChannelManifest>);
+
+impl Manifests {
+    fn load() -> Result<Self> {
+        let mut manifests = Vec::new();
+        for name in toolchain::installed_toolchains()? {
+            let manifest_path = toolchain::toolchains_dir()?
+                .join(name)
+                .join("manifest.toml");
+            if let Ok(manifest) = common::read_channel_manifest(&manifest_path) {
+                manifests.push(manifest);
+            }
+        }
+        Ok(Manifests(manifests))
+    }
+
+    fn find(&self, commit: &str) -> Option<&ChannelManifest> {
+        self.0
+            .iter()
+            .find(|manifest| manifest.commit.as_deref() == Some(commit))
+    }
+}
+
+pub fn run() -> Result<()> {
+    let installs = installs()?;
+    if installs.is_empty() {
+        println!("No installed binaries to verify. Run 'nockup install' first.");
+        return Ok(());
+    }
+
+    let manifests = Manifests::load()?;
+    let (mut verified, mut warnings, mut failures) = (0, 0, 0);
+    for install in &installs {
+        let bin_dir = install.dir.join("bin");
+        let Some(receipt) = Receipt::load(&install.dir)? else {
+            println!("{} {} has no install receipt", "⚠️".yellow(), install.label);
+            println!(
+                "   {}",
+                "It was installed before receipts were recorded; reinstall it to check it".dimmed()
+            );
+            warnings += 1;
+            continue;
+        };
+
+        for (name, component) in &receipt.components {
+            match check(&bin_dir.join(name), component, &manifests, name) {
+                Ok(manifest_checked) => {
+                    let against = if manifest_checked {
+                        "its receipt and channel manifest"
+                    } else {
+                        "its receipt (channel manifest not installed)"
+                    };
+                    println!(
+                        "{} {} ({}) matches {}",
+                        "✅".green(),
+                        name.cyan(),
+                        install.label,
+                        against
+                    );
+                    verified += 1;
+                }
+                Err(e) => {
+                    println!("{} {} ({}): {}", "❌".red(), name.cyan(), install.label, e);
+                    failures += 1;
+                }
+            }
+        }
+
+        // Anything else in `bin` was not put there by nockup.
+        if !install.exclusive {
+            continue;
+        }
+        if let Ok(entries) = fs::read_dir(&bin_dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !receipt.components.contains_key(&name) {
+                    println!(
+                        "{} {} ({}) is not recorded in {}",
+                        "❌".red(),
+                        name.cyan(),
+                        install.label,
+                        install.dir.join(crate::receipt::RECEIPT_FILE).display()
+                    );
+                    failures += 1;
+                }
+            }
+        }
+    }
+
+    println!();
+    if failures > 0 {
+        return Err(anyhow::anyhow!(
+            "{} installed binar{} failed verification; reinstall with 'nockup update' or 'nockup toolchain install'",
+            failures,
+            if failures == 1 { "y" } else { "ies" }
+        ));
+    }
+    if warnings > 0 {
+        println!(
+            "{} {} binaries verified, {} install(s) without a receipt",
+            "⚠️".yellow(),
+            verified,
+            warnings
+        );
+    } else {
+        println!("{} {} binaries verified", "✅".green(), verified);
+    }
+    Ok(())
+}
+
+/// Every directory nockup installs binaries into: `~/.nockup` for nockup
+/// itself, each toolchain, and each added target.
+fn installs() -> Result<Vec<Install>> {
+    let cache_dir = config::nockup_home()?;
+    let mut installs = Vec::new();
+    if cache_dir.join("bin").join("nockup").exists() {
+        installs.push(Install {
+            label: "nockup".to_string(),
+            dir: cache_dir.clone(),
+            exclusive: false,
+        });
+    }
+    for name in toolchain::installed_toolchains()? {
+        installs.push(Install {
+            label: format!("toolchain {}", name),
+            dir: toolchain::toolchains_dir()?.join(name),
+            exclusive: true,
+        });
+    }
+    for triple in target::installed_targets()? {
+        for entry in fs::read_dir(target::targets_dir()?.join(&triple))? {
+            let entry = entry?;
+            if entry.path().join("bin").is_dir() {
+                installs.push(Install {
+                    label: format!(
+                        "toolchain {} for {}",
+                        entry.file_name().to_string_lossy(),
+                        triple
+                    ),
+                    dir: entry.path(),
+                    exclusive: true,
+                });
+            }
+        }
+    }
+    Ok(installs)
+}
+
+/// Check the binary at `path` against `component`, and `component` against
+/// the channel manifest it was installed from.  Returns whether that manifest
+/// was available to check against.
+fn check(
+    path: &Path,
+    component: &ComponentReceipt,
+    manifests: &Manifests,
+    name: &str,
+) -> Result<bool> {
+    if !path.exists() {
+        return Err(anyhow::anyhow!("{} is missing", path.display()));
+    }
+    let hashes = hash_file(path)?;
+    if !component.matches(&hashes) {
+        let (actual, recorded) = if hashes.blake3.eq_ignore_ascii_case(&component.blake3) {
+            (
+                format!("SHA-1 {}", hashes.sha1),
+                format!("SHA-1 {}", component.sha1),
+            )
+        } else {
+            (
+                format!("BLAKE3 {}", hashes.blake3),
+                format!("BLAKE3 {}", component.blake3),
+            )
+        };
+        return Err(anyhow::anyhow!(
+            "{} has been modified since it was installed at {}: {}, expected {}",
+            path.display(),
+            component.installed,
+            actual,
+            recorded
+        ));
+    }
+
+    let Some(manifest) = component
+        .commit
+        .as_deref()
+        .and_then(|commit| manifests.find(commit))
+    else {
+        return Ok(false);
+    };
+    let target = manifest.target(name, &component.architecture)?;
+    if !target
+        .hash_blake3
+        .eq_ignore_ascii_case(&component.archive_blake3)
+        || !target
+            .hash_sha1
+            .eq_ignore_ascii_case(&component.archive_sha1)
+    {
+        return Err(anyhow::anyhow!(
+            "its receipt records archive BLAKE3 {}, but the channel manifest gives {}",
+            component.archive_blake3,
+            target.hash_blake3
+        ));
+    }
+    Ok(true)
+}
@@ -0,0 +1,527 @@
+//! `config.toml` and the directory holding the whole installation.
+//!
+//! Everything nockup installs lives under `~/.nockup`, or under `NOCKUP_HOME`
+//! if it is set, so tests, CI and shared machines can keep separate
+//! installations.  The config file carries a schema version; older files are
+//! migrated when loaded, and files written by a newer nockup are rejected.
+//! Saving edits the existing file in place, so comments and formatting
+//! survive `nockup config set` and friends.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::channel::ChannelSpec;
+use crate::commands::common;
+use crate::{lock, manifest, pgp, toolchain};
+
+/// Environment variable overriding the installation directory.
+pub const HOME_ENV: &str = "NOCKUP_HOME";
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// The config schema this version of nockup writes.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// The directory holding the nockup installation: `NOCKUP_HOME`, or
+/// `~/.nockup`.
+pub fn nockup_home() -> Result<PathBuf> {
+    if let Some(home) = std::env::var_os(HOME_ENV).filter(|home| !home.is_empty()) {
+        return Ok(PathBuf::from(home));
+    }
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+    Ok(home.join(".nockup"))
+}
+
+pub fn config_path() -> Result<PathBuf> {
+    Ok(nockup_home()?.join(CONFIG_FILE))
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NockupConfig {
+    /// Schema version; files without one predate versioning.
+    #[serde(rename = "config-version", default)]
+    pub config_version: u32,
+    /// Channel spec that installs and updates follow.
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    /// Target triple binaries are downloaded for.
+    #[serde(default = "common::get_target_identifier")]
+    pub architecture: String,
+    /// Toolchain the `hoon` and `hoonc` proxies run by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolchain: Option<String>,
+    /// Installation profile; see `ChannelManifest::profile_packages`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Tag or commit of the templates repository to use instead of the
+    /// tracked branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub templates_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "MirrorsConfig::is_empty")]
+    pub mirrors: MirrorsConfig,
+    #[serde(default, skip_serializing_if = "SigningConfig::is_empty")]
+    pub signing: SigningConfig,
+    /// Settings this version of nockup does not know, kept when saving.
+    #[serde(flatten)]
+    pub other: toml::Table,
+}
+
+/// The `[mirrors]` table; see `common::Mirrors`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MirrorsConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub releases: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub templates: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub templates_branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub templates_api: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub templates_archive: Option<String>,
+}
+
+impl MirrorsConfig {
+    fn is_empty(&self) -> bool {
+        self.api.is_none()
+            && self.releases.is_none()
+            && self.templates.is_none()
+            && self.templates_branch.is_none()
+            && self.templates_api.is_none()
+            && self.templates_archive.is_none()
+    }
+}
+
+/// The `[signing]` table; see `pgp::trusted_keyring`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SigningConfig {
+    /// Whether the bundled Zorp key is trusted (default true).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundled_key: Option<bool>,
+    /// Additional trusted key files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<PathBuf>,
+}
+
+impl SigningConfig {
+    fn is_empty(&self) -> bool {
+        self.bundled_key.is_none() && self.keys.is_empty()
+    }
+}
+
+fn default_channel() -> String {
+    "stable".to_string()
+}
+
+impl Default for NockupConfig {
+    fn default() -> Self {
+        NockupConfig {
+            config_version: CONFIG_VERSION,
+            channel: default_channel(),
+            architecture: common::get_target_identifier(),
+            toolchain: None,
+            profile: None,
+            templates_ref: None,
+            mirrors: MirrorsConfig::default(),
+            signing: SigningConfig::default(),
+            other: toml::Table::new(),
+        }
+    }
+}
+
+impl NockupConfig {
+    /// Load `config.toml`, saving it back if it had to be migrated.
+    pub fn load() -> Result<Self> {
+        let config_path = config_path()?;
+        if !config_path.exists() {
+            return Err(anyhow::anyhow!(
+                "Config file not found. Please run 'nockup install' first."
+            ));
+        }
+        let content =
+            std::fs::read_to_string(&config_path).context("Failed to read config file")?;
+        let config = Self::parse(&content)?;
+        if config.config_version != Self::stored_version(&content) {
+            // Only persist the migration while holding the home lock, so it
+            // cannot overwrite a change another nockup is making.  If the lock
+            // is taken, the migrated config is used as is and saved next time.
+            if let Some(_lock) = lock::try_acquire()? {
+                let content =
+                    std::fs::read_to_string(&config_path).context("Failed to read config file")?;
+                let config = Self::parse(&content)?;
+                if config.config_version != Self::stored_version(&content) {
+                    config.save()?;
+                }
+                return Ok(config);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Load `config.toml`, or the defaults if there is none yet.
+    pub fn load_or_default() -> Result<Self> {
+        if config_path()?.exists() {
+            Self::load()
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Parse and migrate the contents of a config file.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config: NockupConfig = toml::from_str(content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file: {}", e.message()))?;
+        if config.config_version > CONFIG_VERSION {
+            return Err(anyhow::anyhow!(
+                "config.toml has version {}, but this nockup only understands up to version {}; run 'nockup self update'",
+                config.config_version,
+                CONFIG_VERSION
+            ));
+        }
+
+        if config.config_version < 1 {
+            // `install.sh` used to record `uname -m` rather than a target triple.
+            if !config.architecture.contains('-') {
+                config.architecture = common::get_target_identifier();
+            }
+        }
+        config.config_version = CONFIG_VERSION;
+        Ok(config)
+    }
+
+    fn stored_version(content: &str) -> u32 {
+        toml::from_str::<toml::Table>(content)
+            .ok()
+            .and_then(|table| table.get("config-version")?.as_integer())
+            .map_or(0, |version| version as u32)
+    }
+
+    /// Write the config back to `config.toml`, changing only the values that
+    /// differ so the file keeps its comments and layout.
+    pub fn save(&self) -> Result<()> {
+        let config_path = config_path()?;
+        if let Some(parent) = config_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut document = std::fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|content| content.parse::<toml_edit::DocumentMut>().ok())
+            .unwrap_or_default();
+        let updated: toml_edit::DocumentMut = toml::to_string(self)?.parse()?;
+        merge_table(document.as_table_mut(), updated.as_table());
+        std::fs::write(&config_path, document.to_string())
+            .context("Failed to write config file")?;
+        Ok(())
+    }
+
+    /// The value stored under a dotted `key`, e.g. `mirrors.api`, or `None`
+    /// if it is not set.
+    pub fn get(&self, key: &str) -> Result<Option<toml::Value>> {
+        let table = toml::Table::try_from(self)?;
+        Ok(lookup(&table, key).cloned())
+    }
+
+    /// Store `value` under a dotted `key`, checking it has the right type.
+    pub fn set(&mut self, key: &str, value: toml::Value) -> Result<()> {
+        let mut table = toml::Table::try_from(&*self)?;
+        let (parents, name) = split_key(key);
+        let mut parent = &mut table;
+        for part in parents {
+            parent = parent
+                .entry(part)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| anyhow::anyhow!("'{}' in config.toml is not a table", part))?;
+        }
+        parent.insert(name.to_string(), value);
+        *self = table.try_into().map_err(|e: toml::de::Error| {
+            anyhow::anyhow!("Invalid value for '{}': {}", key, e.message())
+        })?;
+        Ok(())
+    }
+
+    /// Remove the value stored under a dotted `key`, returning whether it was
+    /// set.  Keys with a default fall back to it.
+    pub fn unset(&mut self, key: &str) -> Result<bool> {
+        let mut table = toml::Table::try_from(&*self)?;
+        let (parents, name) = split_key(key);
+        let mut parent = Some(&mut table);
+        for part in parents {
+            parent = parent.and_then(|table| table.get_mut(part)?.as_table_mut());
+        }
+        let removed = parent.and_then(|table| table.remove(name)).is_some();
+        *self = table.try_into()?;
+        Ok(removed)
+    }
+
+    /// The installation profile, or the default one if none is set.
+    pub fn profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or(manifest::DEFAULT_PROFILE)
+    }
+}
+
+fn split_key(key: &str) -> (Vec<&str>, &str) {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let name = parts.pop().unwrap_or_default();
+    (parts, name)
+}
+
+/// Look up a dotted `key` in a parsed config file.
+pub fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let (parents, name) = split_key(key);
+    let mut parent = table;
+    for part in parents {
+        parent = parent.get(part)?.as_table()?;
+    }
+    parent.get(name)
+}
+
+/// Make `existing` hold the same entries as `updated`, leaving entries whose
+/// value is unchanged untouched so their comments and formatting survive.
+fn merge_table(existing: &mut toml_edit::Table, updated: &toml_edit::Table) {
+    existing.retain(|key, _| updated.contains_key(key));
+    for (key, item) in updated.iter() {
+        match (existing.get_mut(key), item) {
+            (Some(toml_edit::Item::Table(old)), toml_edit::Item::Table(new)) => {
+                merge_table(old, new)
+            }
+            (Some(toml_edit::Item::Value(old)), toml_edit::Item::Value(new)) => {
+                let mut bare = old.clone();
+                bare.decor_mut().clear();
+                if bare.to_string() != new.to_string() {
+                    let decor = old.decor().clone();
+                    *old = new.clone();
+                    *old.decor_mut() = decor;
+                }
+            }
+            _ => {
+                existing.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// The type of value a setting holds.
+#[derive(Clone, Copy)]
+pub enum Kind {
+    /// A string, checked by the given function.
+    String(fn(&str) -> Result<()>),
+    Bool,
+    /// A list of file paths.
+    Paths,
+}
+
+/// A setting `nockup config` can read and change.
+pub struct Setting {
+    /// Dotted key, e.g. `mirrors.api`.
+    pub key: &'static str,
+    pub kind: Kind,
+    /// Environment variable that takes precedence over the config file.
+    pub env: Option<&'static str>,
+    /// Value used when the setting is not configured anywhere.
+    pub default: fn() -> Option<toml::Value>,
+    pub description: &'static str,
+}
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "channel",
+        kind: Kind::String(validate_channel),
+        env: None,
+        default: || Some(default_channel().into()),
+        description: "Channel that install and update follow",
+    },
+    Setting {
+        key: "architecture",
+        kind: Kind::String(validate_architecture),
+        env: None,
+        default: || Some(common::get_target_identifier().into()),
+        description: "Target triple binaries are downloaded for",
+    },
+    Setting {
+        key: "toolchain",
+        kind: Kind::String(validate_toolchain),
+        env: Some(toolchain::TOOLCHAIN_ENV),
+        default: || None,
+        description: "Toolchain the hoon and hoonc proxies run",
+    },
+    Setting {
+        key: "profile",
+        kind: Kind::String(validate_not_empty),
+        env: None,
+        default: || Some(manifest::DEFAULT_PROFILE.into()),
+        description: "Installation profile",
+    },
+    Setting {
+        key: "templates_ref",
+        kind: Kind::String(validate_git_ref),
+        env: None,
+        default: || None,
+        description: "Tag or commit that project templates are pinned to",
+    },
+    Setting {
+        key: "mirrors.api",
+        kind: Kind::String(validate_url),
+        env: Some("NOCKUP_API_URL"),
+        default: || Some(common::DEFAULT_API_URL.into()),
+        description: "GitHub API base for the nockchain repository",
+    },
+    Setting {
+        key: "mirrors.releases",
+        kind: Kind::String(validate_url),
+        env: Some("NOCKUP_RELEASES_URL"),
+        default: || Some(common::DEFAULT_RELEASES_URL.into()),
+        description: "Base URL release archives are downloaded from",
+    },
+    Setting {
+        key: "mirrors.templates",
+        kind: Kind::String(validate_not_empty),
+        env: Some("NOCKUP_TEMPLATES_URL"),
+        default: || Some(common::DEFAULT_TEMPLATES_URL.into()),
+        description: "Git URL of the templates repository",
+    },
+    Setting {
+        key: "mirrors.templates_branch",
+        kind: Kind::String(validate_not_empty),
+        env: Some("NOCKUP_TEMPLATES_BRANCH"),
+        default: || Some(common::DEFAULT_TEMPLATES_BRANCH.into()),
+        description: "Branch of the templates repository",
+    },
+    Setting {
+        key: "mirrors.templates_api",
+        kind: Kind::String(validate_url),
+        env: Some("NOCKUP_TEMPLATES_API_URL"),
+        default: || common::Mirrors::load().templates_api.map(Into::into),
+        description: "GitHub API base for the templates repository",
+    },
+    Setting {
+        key: "mirrors.templates_archive",
+        kind: Kind::String(validate_url),
+        env: Some("NOCKUP_TEMPLATES_ARCHIVE_URL"),
+        default: || common::Mirrors::load().templates_archive.map(Into::into),
+        description: "Base URL of templates source archives (<commit>.tar.gz)",
+    },
+    Setting {
+        key: "signing.bundled_key",
+        kind: Kind::Bool,
+        env: None,
+        default: || Some(true.into()),
+        description: "Whether the bundled Zorp signing key is trusted",
+    },
+    Setting {
+        key: "signing.keys",
+        kind: Kind::Paths,
+        env: Some(pgp::TRUSTED_KEYS_ENV),
+        default: || Some(toml::Value::Array(Vec::new())),
+        description: "Additional trusted signing key files",
+    },
+];
+
+/// Look up a setting by its dotted key.
+pub fn setting(key: &str) -> Result<&'static Setting> {
+    SETTINGS
+        .iter()
+        .find(|setting| setting.key == key)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown config key '{}'; run 'nockup config list' to see the available keys",
+                key
+            )
+        })
+}
+
+impl Setting {
+    /// Parse values given on the command line into this setting's type.
+    pub fn parse(&self, values: &[String]) -> Result<toml::Value> {
+        if let Kind::Paths = self.kind {
+            return Ok(toml::Value::Array(
+                values.iter().map(|value| value.as_str().into()).collect(),
+            ));
+        }
+        let [value] = values else {
+            return Err(anyhow::anyhow!("'{}' takes a single value", self.key));
+        };
+        match self.kind {
+            Kind::String(validate) => {
+                validate(value)
+                    .map_err(|e| anyhow::anyhow!("Invalid value for '{}': {}", self.key, e))?;
+                Ok(value.as_str().into())
+            }
+            Kind::Bool => match value.as_str() {
+                "true" => Ok(true.into()),
+                "false" => Ok(false.into()),
+                _ => Err(anyhow::anyhow!(
+                    "'{}' must be true or false, not '{}'",
+                    self.key,
+                    value
+                )),
+            },
+            Kind::Paths => unreachable!("handled above"),
+        }
+    }
+}
+
+fn validate_channel(value: &str) -> Result<()> {
+    ChannelSpec::parse(value).map(|_| ())
+}
+
+fn validate_architecture(value: &str) -> Result<()> {
+    if value.split('-').count() < 3 || value.split('-').any(str::is_empty) {
+        return Err(anyhow::anyhow!(
+            "'{}' is not a target triple like {}",
+            value,
+            common::get_target_identifier()
+        ));
+    }
+    Ok(())
+}
+
+fn validate_toolchain(value: &str) -> Result<()> {
+    if !toolchain::is_installed(value)? {
+        return Err(anyhow::anyhow!(
+            "toolchain '{}' is not installed; run 'nockup toolchain list' to see installed toolchains",
+            value
+        ));
+    }
+    Ok(())
+}
+
+/// Check that `value` is a ref name `git check-ref-format --allow-onelevel`
+/// accepts and that cannot be mistaken for a command-line option.
+pub fn validate_git_ref(value: &str) -> Result<()> {
+    let valid = !value.is_empty()
+        && value != "@"
+        && !value.starts_with(['-', '/'])
+        && !value.ends_with(['/', '.'])
+        && !value.contains("..")
+        && !value.contains("//")
+        && !value.contains("@{")
+        && !value
+            .chars()
+            .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c))
+        && value
+            .split('/')
+            .all(|component| !component.starts_with('.') && !component.ends_with(".lock"));
+    if !valid {
+        return Err(anyhow::anyhow!("'{}' is not a valid git ref", value));
+    }
+    Ok(())
+}
+
+fn validate_url(value: &str) -> Result<()> {
+    if !value.starts_with("https://") && !value.starts_with("http://") {
+        return Err(anyhow::anyhow!("'{}' is not an http(s) URL", value));
+    }
+    Ok(())
+}
+
+fn validate_not_empty(value: &str) -> Result<()> {
+    if value.is_empty() {
+        return Err(anyhow::anyhow!("the value may not be empty"));
+    }
+    Ok(())
+}
@@ -0,0 +1,296 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use sha1::{Digest, Sha1};
+
+use crate::commands::common;
+use crate::config;
+
+/// How many times a download is attempted before giving up.  Each retry
+/// resumes from the bytes already on disk.
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// How many release artifacts are downloaded at once.
+pub const CONCURRENT_DOWNLOADS: usize = 4;
+
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// BLAKE3 and SHA-1 digests of a file, as lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hashes {
+    pub blake3: String,
+    pub sha1: String,
+}
+
+#[derive(Default)]
+struct Hasher {
+    blake3: blake3::Hasher,
+    sha1: Sha1,
+}
+
+impl Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        self.blake3.update(bytes);
+        self.sha1.update(bytes);
+    }
+
+    fn finalize(self) -> Hashes {
+        Hashes {
+            blake3: self.blake3.finalize().to_hex().to_string(),
+            sha1: hex::encode(self.sha1.finalize()),
+        }
+    }
+
+    /// Feed the contents of `path` into the hasher without loading it whole.
+    fn update_from_file(&mut self, path: &Path) -> Result<()> {
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut buffer = vec![0; HASH_BUFFER_SIZE];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.update(&buffer[..read]);
+        }
+    }
+}
+
+/// Hash the file at `path`, streaming it from disk.
+pub fn hash_file(path: &Path) -> Result<Hashes> {
+    let mut hasher = Hasher::default();
+    hasher.update_from_file(path)?;
+    Ok(hasher.finalize())
+}
+
+/// Check `hashes` against the digests published in a channel manifest.
+pub fn verify_checksums(hashes: &Hashes, expected_blake3: &str, expected_sha1: &str) -> Result<()> {
+    if !hashes.blake3.eq_ignore_ascii_case(expected_blake3) {
+        return Err(anyhow::anyhow!(
+            "Checksum verification failed: expected {}, got {}",
+            expected_blake3,
+            hashes.blake3
+        ));
+    }
+    println!("{} Blake3 checksum passed.", "✅".green());
+
+    hex::decode(expected_sha1).map_err(|e| anyhow::anyhow!("Invalid hex SHA-1: {}", e))?;
+    if !hashes.sha1.eq_ignore_ascii_case(expected_sha1) {
+        return Err(anyhow::anyhow!(
+            "Checksum verification failed: expected {}, got {}",
+            expected_sha1,
+            hashes.sha1
+        ));
+    }
+    println!("{} SHA1 checksum passed.", "✅".green());
+    Ok(())
+}
+
+/// A release artifact on disk together with its digests.  Downloaded artifacts
+/// are removed when dropped, so nothing is left behind if installation fails
+/// part-way.
+#[derive(Debug)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub hashes: Hashes,
+    temporary: bool,
+}
+
+impl Artifact {
+    /// An artifact that already exists locally and must not be removed.
+    pub fn local(path: PathBuf) -> Result<Self> {
+        let hashes = hash_file(&path)?;
+        Ok(Artifact {
+            path,
+            hashes,
+            temporary: false,
+        })
+    }
+}
+
+impl Drop for Artifact {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+enum Failure {
+    /// The transfer was interrupted; a retry can resume it.
+    Interrupted(anyhow::Error),
+    /// Retrying will not help.
+    Fatal(anyhow::Error),
+}
+
+/// Download `url` into `~/.nockup/downloads`, hashing it as it streams to disk.
+///
+/// The transfer is written to a `.part` file first.  If the connection drops,
+/// the download is retried from where it stopped using an HTTP `Range`
+/// request, and a `.part` file left over from an earlier run is resumed the
+/// same way.
+pub async fn download_file(url: &str) -> Result<Artifact> {
+    let downloads_dir = config::nockup_home()?.join("downloads");
+    fs::create_dir_all(&downloads_dir)
+        .with_context(|| format!("Failed to create {}", downloads_dir.display()))?;
+
+    // Prefix with a digest of the URL so artifacts of the same name from
+    // different releases never share a partial file.
+    let file_name = format!(
+        "{}-{}",
+        &blake3::hash(url.as_bytes()).to_hex()[..12],
+        common::artifact_file_name(url)
+    );
+    let part_path = downloads_dir.join(format!("{}.part", file_name));
+    let path = downloads_dir.join(file_name);
+
+    let mut attempt = 1;
+    loop {
+        match download_part(url, &part_path).await {
+            Ok(hashes) => {
+                fs::rename(&part_path, &path)
+                    .with_context(|| format!("Failed to move {}", part_path.display()))?;
+                return Ok(Artifact {
+                    path,
+                    hashes,
+                    temporary: true,
+                });
+            }
+            Err(Failure::Interrupted(e)) if attempt < DOWNLOAD_ATTEMPTS => {
+                progress_bars().suspend(|| {
+                    println!(
+                        "{} Download interrupted ({}), resuming...",
+                        "⚠️".yellow(),
+                        e
+                    )
+                });
+                attempt += 1;
+                tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+            }
+            // Keep the partial file so the next run can resume it.
+            Err(Failure::Interrupted(e)) => return Err(e),
+            Err(Failure::Fatal(e)) => {
+                let _ = fs::remove_file(&part_path);
+                return Err(e);
+            }
+        }
+    }
+}
+
+async fn download_part(url: &str, part_path: &Path) -> Result<Hashes, Failure> {
+    let existing = part_path.metadata().map(|m| m.len()).unwrap_or(0);
+
+    let client = reqwest::Client::new();
+    let mut request = client.get(url).header("User-Agent", "nockup");
+    if existing > 0 {
+        request = request.header(RANGE, format!("bytes={}-", existing));
+    }
+    let mut response = request.send().await.map_err(|e| {
+        Failure::Interrupted(anyhow::anyhow!(
+            "Failed to download file from '{}': {}",
+            url,
+            e
+        ))
+    })?;
+
+    let status = response.status();
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file does not match what the server has; start over.
+        let _ = fs::remove_file(part_path);
+        return Err(Failure::Interrupted(anyhow::anyhow!(
+            "Server rejected resuming '{}'",
+            url
+        )));
+    }
+    if !status.is_success() {
+        let error = anyhow::anyhow!("Failed to download file from '{}': HTTP {}", url, status);
+        return Err(if status.is_server_error() {
+            Failure::Interrupted(error)
+        } else {
+            Failure::Fatal(error)
+        });
+    }
+
+    let mut hasher = Hasher::default();
+    let resuming = existing > 0 && status == StatusCode::PARTIAL_CONTENT;
+    let mut file = if resuming {
+        hasher.update_from_file(part_path).map_err(Failure::Fatal)?;
+        OpenOptions::new().append(true).open(part_path)
+    } else {
+        File::create(part_path)
+    }
+    .context("Failed to create download file")
+    .map_err(Failure::Fatal)?;
+
+    let offset = if resuming { existing } else { 0 };
+    let total = response.content_length().map(|length| length + offset);
+    let progress = progress_bar(common::artifact_file_name(url), total, offset);
+
+    let mut received = offset;
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                progress.abandon();
+                return Err(Failure::Interrupted(anyhow::anyhow!(
+                    "Failed to download file from '{}': {}",
+                    url,
+                    e
+                )));
+            }
+        };
+        file.write_all(&chunk)
+            .context("Failed to write download file")
+            .map_err(Failure::Fatal)?;
+        hasher.update(&chunk);
+        received += chunk.len() as u64;
+        progress.set_position(received);
+    }
+    progress.finish_and_clear();
+
+    if let Some(total) = total {
+        if received < total {
+            return Err(Failure::Interrupted(anyhow::anyhow!(
+                "connection closed after {} of {} bytes",
+                received,
+                total
+            )));
+        }
+    }
+    Ok(hasher.finalize())
+}
+
+/// The progress bars of downloads in flight, each drawn on its own line.
+fn progress_bars() -> &'static MultiProgress {
+    static PROGRESS_BARS: OnceLock<MultiProgress> = OnceLock::new();
+    PROGRESS_BARS.get_or_init(MultiProgress::new)
+}
+
+fn progress_bar(name: &str, total: Option<u64>, position: u64) -> ProgressBar {
+    let progress = match total {
+        Some(total) => ProgressBar::new(total).with_style(
+            ProgressStyle::with_template(
+                "{msg} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} ({eta})",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        ),
+        None => ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("{spinner} {msg} {bytes} {bytes_per_sec}").unwrap(),
+        ),
+    };
+    progress.set_message(name.to_string());
+    progress.set_position(position);
+    progress_bars().add(progress)
+}
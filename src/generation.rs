@@ -0,0 +1,246 @@
+//! Generations of the installed `nockup` binary and default toolchain.
+//!
+//! Updates are downloaded and verified into a staging directory before any
+//! installed file is touched.  Switching to the staged generation saves the
+//! current one under `~/.nockup/previous`, from where `nockup rollback` can
+//! restore it; if the switch fails partway, the saved generation is put back
+//! instead.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::commands::common;
+use crate::config::{self, NockupConfig};
+use crate::proxy;
+use crate::receipt::Receipt;
+use crate::toolchain;
+
+/// File recording what a saved generation consists of.
+const GENERATION_FILE: &str = "generation.toml";
+
+const NOCKUP_BINARY: &str = "nockup";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Generation {
+    /// The default toolchain of this generation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toolchain: Option<String>,
+}
+
+/// An empty staging directory under `~/.nockup/staging`.  Anything left over
+/// from an interrupted update is discarded.
+pub fn staging_dir() -> Result<PathBuf> {
+    let staging_dir = config::nockup_home()?.join("staging");
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir)
+            .with_context(|| format!("Failed to clear {}", staging_dir.display()))?;
+    }
+    fs::create_dir_all(&staging_dir)
+        .with_context(|| format!("Failed to create {}", staging_dir.display()))?;
+    Ok(staging_dir)
+}
+
+/// `~/.nockup/previous`, after finishing any `replace_previous` that was
+/// interrupted between moving the old generation aside and deleting it.
+fn previous_dir() -> Result<PathBuf> {
+    let cache_dir = config::nockup_home()?;
+    let previous_dir = cache_dir.join("previous");
+    let old_dir = cache_dir.join("previous.old");
+    if old_dir.exists() {
+        if previous_dir.exists() {
+            fs::remove_dir_all(&old_dir)
+                .with_context(|| format!("Failed to remove {}", old_dir.display()))?;
+        } else {
+            fs::rename(&old_dir, &previous_dir)
+                .with_context(|| format!("Failed to restore {}", previous_dir.display()))?;
+        }
+    }
+    Ok(previous_dir)
+}
+
+/// The saved generation that `nockup rollback` would restore, if any.
+fn previous_generation() -> Result<Option<Generation>> {
+    let previous_dir = previous_dir()?;
+    if !previous_dir.join(GENERATION_FILE).exists() {
+        return Ok(None);
+    }
+    read_generation(&previous_dir).map(Some)
+}
+
+/// Make `toolchain_name` the default toolchain and, if given, `staged_nockup`
+/// the installed nockup binary, saving the current generation first.  If any
+/// step fails, the current generation is put back.
+pub fn switch(staged_nockup: Option<&Path>, toolchain_name: &str) -> Result<()> {
+    let saved = save_current()?;
+
+    let result = (|| {
+        if let Some(staged_nockup) = staged_nockup {
+            let cache_dir = config::nockup_home()?;
+            let bin_dir = cache_dir.join("bin");
+            fs::create_dir_all(&bin_dir)?;
+            common::replace_executable(staged_nockup, &bin_dir.join(NOCKUP_BINARY))?;
+            // The receipt sits beside the staged `bin` directory.
+            if let Some(staged_dir) = staged_nockup.parent().and_then(Path::parent) {
+                Receipt::copy_component(staged_dir, &cache_dir, NOCKUP_BINARY)?;
+            }
+        }
+        toolchain::set_default_toolchain(toolchain_name)
+    })();
+
+    match saved {
+        Some(saved) => finish_switch(&saved, result),
+        None => result,
+    }
+}
+
+/// Restore the previous generation, which in turn becomes the one that a
+/// further rollback would return to.
+pub fn rollback() -> Result<Generation> {
+    let previous_dir = previous_dir()?;
+    let generation = previous_generation()?
+        .ok_or_else(|| anyhow::anyhow!("No previous generation to roll back to"))?;
+    if let Some(name) = &generation.toolchain {
+        if !toolchain::is_installed(name)? {
+            return Err(anyhow::anyhow!(
+                "Cannot roll back: toolchain '{}' is no longer installed",
+                name
+            ));
+        }
+    }
+
+    let saved = save_current()?;
+    let result = install_nockup(&previous_dir).and_then(|()| match &generation.toolchain {
+        Some(name) => toolchain::set_default_toolchain(name),
+        None => Ok(()),
+    });
+    match saved {
+        Some(saved) => finish_switch(&saved, result)?,
+        None => {
+            result?;
+            fs::remove_dir_all(&previous_dir)?;
+        }
+    }
+    Ok(generation)
+}
+
+/// Complete a switch away from the generation saved in `saved_dir`: keep it as
+/// the previous generation if the switch succeeded, and put it back if not.
+fn finish_switch(saved_dir: &Path, result: Result<()>) -> Result<()> {
+    let Err(e) = result else {
+        return replace_previous(saved_dir);
+    };
+    if let Err(restore_error) = restore(saved_dir) {
+        println!(
+            "{} Could not restore the current generation from {}: {}",
+            "⚠️".yellow(),
+            saved_dir.display(),
+            restore_error
+        );
+    }
+    Err(e)
+}
+
+fn read_generation(dir: &Path) -> Result<Generation> {
+    let generation_path = dir.join(GENERATION_FILE);
+    let content = fs::read_to_string(&generation_path)
+        .with_context(|| format!("Failed to read {}", generation_path.display()))?;
+    toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", generation_path.display()))
+}
+
+/// Put back the generation saved in `saved_dir` after a failed switch, then
+/// discard the saved copy.
+fn restore(saved_dir: &Path) -> Result<()> {
+    let generation = read_generation(saved_dir)?;
+    install_nockup(saved_dir)?;
+    let mut config = NockupConfig::load_or_default()?;
+    config.toolchain = generation.toolchain;
+    config.save()?;
+    // Relink the proxies to the restored binary.  Installing them is the
+    // likeliest step to have failed, so a failure here is not fatal: proxies
+    // left linked to the other binary still work.
+    let _ = proxy::install_proxies();
+    fs::remove_dir_all(saved_dir)
+        .with_context(|| format!("Failed to remove {}", saved_dir.display()))
+}
+
+/// Install the nockup binary and receipt saved in `dir`, if there is one,
+/// leaving `dir` intact.
+fn install_nockup(dir: &Path) -> Result<()> {
+    let saved_nockup = dir.join(NOCKUP_BINARY);
+    if saved_nockup.exists() {
+        let cache_dir = config::nockup_home()?;
+        let bin_dir = cache_dir.join("bin");
+        fs::create_dir_all(&bin_dir)?;
+        // Installing moves the binary, so install a link to it instead.
+        let installing = bin_dir.join(format!(".{}.restore", NOCKUP_BINARY));
+        let _ = fs::remove_file(&installing);
+        link_or_copy(&saved_nockup, &installing)?;
+        common::replace_executable(&installing, &bin_dir.join(NOCKUP_BINARY))?;
+        Receipt::copy_component(dir, &cache_dir, NOCKUP_BINARY)?;
+    }
+    Ok(())
+}
+
+/// Hard link `from` to `to`, or copy it where links are not possible.
+fn link_or_copy(from: &Path, to: &Path) -> Result<()> {
+    if fs::hard_link(from, to).is_err() {
+        fs::copy(from, to)
+            .with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+    }
+    Ok(())
+}
+
+/// Save the current nockup binary and default toolchain into a fresh
+/// directory.  Returns `None` if nothing is installed yet.
+fn save_current() -> Result<Option<PathBuf>> {
+    let cache_dir = config::nockup_home()?;
+    let nockup = cache_dir.join("bin").join(NOCKUP_BINARY);
+    let generation = Generation {
+        toolchain: toolchain::default_toolchain()?,
+    };
+    if !nockup.exists() && generation.toolchain.is_none() {
+        return Ok(None);
+    }
+
+    let saved_dir = cache_dir.join("previous.new");
+    if saved_dir.exists() {
+        fs::remove_dir_all(&saved_dir)?;
+    }
+    fs::create_dir_all(&saved_dir)?;
+    if nockup.exists() {
+        // A hard link keeps the old binary alive once the new one is renamed
+        // over it.
+        link_or_copy(&nockup, &saved_dir.join(NOCKUP_BINARY))
+            .context("Failed to save the current nockup binary")?;
+        Receipt::copy_component(&cache_dir, &saved_dir, NOCKUP_BINARY)?;
+    }
+    fs::write(
+        saved_dir.join(GENERATION_FILE),
+        toml::to_string(&generation)?,
+    )
+    .context("Failed to write generation file")?;
+    Ok(Some(saved_dir))
+}
+
+/// Make `saved_dir` the previous generation.  The old one is moved aside
+/// before the new one takes its place, so there is always one to roll back to.
+fn replace_previous(saved_dir: &Path) -> Result<()> {
+    let previous_dir = previous_dir()?;
+    let old_dir = previous_dir.with_file_name("previous.old");
+    if previous_dir.exists() {
+        fs::rename(&previous_dir, &old_dir)
+            .with_context(|| format!("Failed to move {} aside", previous_dir.display()))?;
+    }
+    fs::rename(saved_dir, &previous_dir)
+        .with_context(|| format!("Failed to create {}", previous_dir.display()))?;
+    if old_dir.exists() {
+        fs::remove_dir_all(&old_dir)
+            .with_context(|| format!("Failed to remove {}", old_dir.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,218 @@
+//! Requests to the GitHub REST API, or a mirror of it.
+//!
+//! Responses are cached under `~/.nockup/cache/api` and revalidated with
+//! `If-None-Match`, which GitHub does not count against the rate limit, and
+//! each URL is requested at most once per run.  `GITHUB_TOKEN` is sent to
+//! api.github.com to raise the rate limit.  Paginated listings are followed
+//! through their `Link: rel="next"` headers.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use reqwest::header::{HeaderMap, ETAG, IF_NONE_MATCH, LINK, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Environment variable holding a token for authenticated API requests.
+pub const TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// The token is only ever sent here, never to a configured mirror.
+const GITHUB_API: &str = "https://api.github.com/";
+
+/// Responses already fetched during this run, by URL.
+static RESPONSES: OnceLock<Mutex<HashMap<String, Response>>> = OnceLock::new();
+
+#[derive(Clone)]
+struct Response {
+    body: String,
+    /// URL of the next page of a paginated listing.
+    next: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CachedResponse {
+    url: String,
+    etag: Option<String>,
+    body: String,
+    #[serde(default)]
+    next: Option<String>,
+}
+
+/// Fetch and parse the JSON document at `url`.  `what` describes it for error
+/// messages, e.g. "releases".
+pub async fn get_json<T: DeserializeOwned>(url: &str, what: &str) -> Result<T> {
+    Ok(get_json_page(url, what).await?.0)
+}
+
+/// Fetch and parse one page of a paginated JSON listing, along with the URL
+/// of the next page if the response links to one.
+pub async fn get_json_page<T: DeserializeOwned>(
+    url: &str,
+    what: &str,
+) -> Result<(T, Option<String>)> {
+    let response = get(url, what).await?;
+    let page = serde_json::from_str(&response.body)
+        .with_context(|| format!("Failed to parse {} JSON", what))?;
+    Ok((page, response.next))
+}
+
+async fn get(url: &str, what: &str) -> Result<Response> {
+    let responses = RESPONSES.get_or_init(Default::default);
+    if let Some(response) = responses.lock().unwrap().get(url) {
+        return Ok(response.clone());
+    }
+
+    let cache_path = cache_path(url)?;
+    let cached = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<CachedResponse>(&content).ok())
+        .filter(|cached| cached.url == url);
+
+    let mut request = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", "nockup")
+        .header("Accept", "application/vnd.github+json");
+    if url.starts_with(GITHUB_API) {
+        if let Some(token) = std::env::var(TOKEN_ENV)
+            .ok()
+            .filter(|token| !token.is_empty())
+        {
+            request = request.bearer_auth(token);
+        }
+    }
+    if let Some(etag) = cached.as_ref().and_then(|cached| cached.etag.as_deref()) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {} from GitHub API", what))?;
+    let status = response.status();
+    let next = next_link(response.headers());
+    let fetched = match cached {
+        Some(cached) if status == StatusCode::NOT_MODIFIED => Response {
+            body: cached.body,
+            next: next.or(cached.next),
+        },
+        _ if status.is_success() => {
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(String::from);
+            let body = response
+                .text()
+                .await
+                .with_context(|| format!("Failed to read {} response", what))?;
+            if let Some(parent) = cache_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let cached = CachedResponse {
+                url: url.to_string(),
+                etag,
+                body: body.clone(),
+                next: next.clone(),
+            };
+            fs::write(&cache_path, serde_json::to_string(&cached)?)
+                .with_context(|| format!("Failed to write {}", cache_path.display()))?;
+            Response { body, next }
+        }
+        cached => match rate_limit_message(status, response.headers()) {
+            Some(message) => match cached {
+                // A stale answer beats none when the limit has been hit.
+                Some(cached) => {
+                    println!(
+                        "{} {}; using the cached {} response",
+                        "⚠️".yellow(),
+                        message,
+                        what
+                    );
+                    Response {
+                        body: cached.body,
+                        next: cached.next,
+                    }
+                }
+                None => return Err(anyhow::anyhow!("Failed to fetch {}: {}", what, message)),
+            },
+            None => return Err(anyhow::anyhow!("Failed to fetch {}: HTTP {}", what, status)),
+        },
+    };
+
+    responses
+        .lock()
+        .unwrap()
+        .insert(url.to_string(), fetched.clone());
+    Ok(fetched)
+}
+
+/// The `rel="next"` target of a `Link` header, as GitHub sends on every page
+/// of a listing but the last.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    link.split(',').find_map(|entry| {
+        let (target, params) = entry.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"")
+            .then(|| {
+                target
+                    .trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+fn cache_path(url: &str) -> Result<PathBuf> {
+    let key = blake3::hash(url.as_bytes()).to_hex();
+    Ok(config::nockup_home()?
+        .join("cache")
+        .join("api")
+        .join(format!("{}.json", &key[..16])))
+}
+
+/// Describe a rate-limited response, or return `None` if `status` is some
+/// other failure.
+fn rate_limit_message(status: StatusCode, headers: &HeaderMap) -> Option<String> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let hint = if std::env::var(TOKEN_ENV).is_ok_and(|token| !token.is_empty()) {
+        String::new()
+    } else {
+        format!(" (set {} to raise the limit)", TOKEN_ENV)
+    };
+    if let Some(seconds) = header(RETRY_AFTER.as_str()).and_then(|s| s.parse::<u64>().ok()) {
+        return Some(format!(
+            "GitHub API rate limit exceeded (HTTP {}); retry in {} seconds{}",
+            status.as_u16(),
+            seconds,
+            hint
+        ));
+    }
+    if header("x-ratelimit-remaining") != Some("0") {
+        return None;
+    }
+    let reset = header("x-ratelimit-reset")
+        .and_then(|reset| reset.parse::<i64>().ok())
+        .and_then(|reset| chrono::DateTime::from_timestamp(reset, 0))
+        .map(|reset| format!("; it resets at {}", reset.format("%Y-%m-%d %H:%M:%S UTC")))
+        .unwrap_or_default();
+    Some(format!(
+        "GitHub API rate limit exceeded (HTTP {}){}{}",
+        status.as_u16(),
+        reset,
+        hint
+    ))
+}
@@ -7,6 +7,9 @@ use anyhow::{Context, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 
+use crate::receipt::{self, LibraryReceipt, LibraryReceipts};
+use crate::{config, lock};
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LibrarySpec {
     pub url: String,
@@ -154,10 +157,7 @@ fn validate_library_spec(spec: &LibrarySpec) -> Result<()> {
 }
 
 fn get_library_cache_dir() -> Result<PathBuf> {
-    let cache_dir = dirs::home_dir()
-        .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
-        .join(".nockup")
-        .join("library_cache");
+    let cache_dir = config::nockup_home()?.join("library_cache");
 
     fs::create_dir_all(&cache_dir).context("Failed to create library cache directory")?;
 
@@ -166,7 +166,7 @@ fn get_library_cache_dir() -> Result<PathBuf> {
 
 async fn fetch_library_repo(
     cache_dir: &Path,
-    lib_name: &str,
+    _lib_name: &str,
     spec: &LibrarySpec,
 ) -> Result<PathBuf> {
     // Create a unique directory name based on URL and commit/branch
@@ -179,6 +179,10 @@ async fn fetch_library_repo(
 
     let repo_cache_dir = cache_dir.join(format!("{}_{}", repo_name, unique_id));
 
+    // The clone and its receipt go into ~/.nockup, so hold its lock even when
+    // the caller does not.
+    let _lock = lock::acquire().await?;
+
     // If already cached, return it
     if repo_cache_dir.exists() {
         return Ok(repo_cache_dir);
@@ -188,11 +192,11 @@ async fn fetch_library_repo(
     println!("    ⬇️ Cloning repository...");
 
     let mut git_cmd = Command::new("git");
-    git_cmd.args(&["clone", &spec.url]);
+    git_cmd.args(["clone", &spec.url]);
 
     // If branch specified, clone that branch
     if let Some(branch) = &spec.branch {
-        git_cmd.args(&["--branch", branch]);
+        git_cmd.args(["--branch", branch]);
     }
 
     git_cmd.arg(&repo_cache_dir);
@@ -209,7 +213,7 @@ async fn fetch_library_repo(
     // If commit specified, checkout that commit
     if let Some(commit) = &spec.commit {
         let checkout_output = Command::new("git")
-            .args(&["checkout", commit])
+            .args(["checkout", commit])
             .current_dir(&repo_cache_dir)
             .output()
             .context("Failed to checkout commit")?;
@@ -222,6 +226,24 @@ async fn fetch_library_repo(
         }
     }
 
+    let head_output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(&repo_cache_dir)
+        .output()
+        .context("Failed to read the checked out commit")?;
+    LibraryReceipts::add(
+        cache_dir,
+        &format!("{}_{}", repo_name, unique_id),
+        LibraryReceipt {
+            url: spec.url.clone(),
+            branch: spec.branch.clone(),
+            commit: String::from_utf8_lossy(&head_output.stdout)
+                .trim()
+                .to_string(),
+            fetched: receipt::now(),
+        },
+    )?;
+
     Ok(repo_cache_dir)
 }
 
@@ -267,8 +289,8 @@ fn find_library_source_dir(repo_dir: &Path, spec: &LibrarySpec) -> Result<PathBu
 fn copy_library_files(
     source_dir: &Path,
     dest_lib_dir: &Path,
-    lib_name: &str,
-    spec: &LibrarySpec,
+    _lib_name: &str,
+    _spec: &LibrarySpec,
 ) -> Result<()> {
     // Always use flattened approach - copy contents directly to appropriate directories
     let project_hoon_dir = dest_lib_dir.parent().unwrap(); // Get /hoon from /hoon/lib
@@ -278,18 +300,14 @@ fn copy_library_files(
     Ok(())
 }
 
-fn ensure_directory_exists(dir: &Path) -> Result<()> {
-    fs::create_dir_all(dir)
-        .with_context(|| format!("Failed to create directory '{}'", dir.display()))
-}
-
 fn copy_single_file(repo_dir: &Path, project_lib_dir: &Path, file_path: &str) -> Result<()> {
     let source_file = repo_dir.join(file_path);
 
     // Check if the source file exists
     if !source_file.exists() {
         return Err(anyhow::anyhow!(
-            "File '{}' not found in repository", file_path
+            "File '{}' not found in repository",
+            file_path
         ));
     }
 
@@ -353,15 +371,13 @@ fn copy_top_level_library(src_dir: &Path, dest_dir: &Path, root_src: &Path) -> R
                 format!("Failed to create directory '{}'", dest_subdir.display())
             })?;
             copy_directory_contents(&src_path, &dest_subdir, root_src)?;
-        } else {
-            if should_copy_file(&src_path) {
-                let dest_path = dest_dir.join(&file_name);
-                fs::copy(&src_path, &dest_path)
-                    .with_context(|| format!("Failed to copy file '{}'", src_path.display()))?;
+        } else if should_copy_file(&src_path) {
+            let dest_path = dest_dir.join(&file_name);
+            fs::copy(&src_path, &dest_path)
+                .with_context(|| format!("Failed to copy file '{}'", src_path.display()))?;
 
-                let relative_src = src_path.strip_prefix(root_src).unwrap_or(&src_path);
-                println!("      copy {}", relative_src.display());
-            }
+            let relative_src = src_path.strip_prefix(root_src).unwrap_or(&src_path);
+            println!("      copy {}", relative_src.display());
         }
     }
 
@@ -0,0 +1,189 @@
+//! An advisory lock on the nockup home directory.
+//!
+//! Commands that change anything under `~/.nockup` hold an exclusive lock on
+//! `~/.nockup/nockup.lock` while they run, so that two of them never rewrite
+//! the same files at once, e.g. `nockup update` replacing the templates while
+//! `nockup init` copies from them.  The lock file records the PID of the
+//! process holding the lock.  The lock itself is released by the operating
+//! system when that process exits, so a crashed nockup never leaves it held.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::config;
+
+pub const LOCK_FILE: &str = "nockup.lock";
+
+/// Seconds to wait for another nockup process to release the lock.
+pub const TIMEOUT_ENV: &str = "NOCKUP_LOCK_TIMEOUT";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The locked file while this process holds the lock, and the number of
+/// guards for it.
+static HELD: Mutex<Option<(File, usize)>> = Mutex::new(None);
+
+/// Holds the lock on the nockup home directory until dropped.
+#[must_use]
+pub struct HomeLock(());
+
+impl Drop for HomeLock {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, guards)) = held.as_mut() {
+            *guards -= 1;
+            if *guards == 0 {
+                // Closing the file releases the lock.
+                *held = None;
+            }
+        }
+    }
+}
+
+/// Lock the nockup home directory, waiting for another nockup process to
+/// release it if necessary.  Locking again in a process that already holds
+/// the lock succeeds immediately.  The wait sleeps on the async runtime, so
+/// it never ties up a worker thread.
+pub async fn acquire() -> Result<HomeLock> {
+    if let Some(lock) = reenter() {
+        return Ok(lock);
+    }
+
+    let (home, lock_path, mut file) = open_lock_file()?;
+    if !try_lock(&file)? {
+        let timeout = timeout()?;
+        println!(
+            "{} Waiting for lock on {} held by PID {}...",
+            "⏳".yellow(),
+            home.display(),
+            holder(&mut file)
+        );
+        let start = Instant::now();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if try_lock(&file)? {
+                break;
+            }
+            if start.elapsed() >= timeout {
+                return Err(anyhow::anyhow!(
+                    "Timed out after {}s waiting for the lock on {} held by PID {}; set {} to wait longer",
+                    timeout.as_secs(),
+                    home.display(),
+                    holder(&mut file),
+                    TIMEOUT_ENV
+                ));
+            }
+        }
+    }
+
+    // Another task in this process cannot have taken the lock meanwhile:
+    // `file` is a separate open file description, so its flock excludes
+    // theirs.
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    hold(&mut held, &lock_path, file)
+}
+
+/// Lock the nockup home directory unless another nockup process holds it, in
+/// which case this returns `None` without waiting.
+pub fn try_acquire() -> Result<Option<HomeLock>> {
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(lock) = add_guard(&mut held) {
+        return Ok(Some(lock));
+    }
+
+    let (_, lock_path, file) = open_lock_file()?;
+    if !try_lock(&file)? {
+        return Ok(None);
+    }
+    hold(&mut held, &lock_path, file).map(Some)
+}
+
+/// A further guard for the lock if this process already holds it.
+fn reenter() -> Option<HomeLock> {
+    add_guard(&mut HELD.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn add_guard(held: &mut Option<(File, usize)>) -> Option<HomeLock> {
+    let (_, guards) = held.as_mut()?;
+    *guards += 1;
+    Some(HomeLock(()))
+}
+
+fn open_lock_file() -> Result<(PathBuf, PathBuf, File)> {
+    let home = config::nockup_home()?;
+    fs::create_dir_all(&home).with_context(|| format!("Failed to create {}", home.display()))?;
+    let lock_path = home.join(LOCK_FILE);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+    Ok((home, lock_path, file))
+}
+
+/// Record this process as the holder of the freshly locked `file`.
+fn hold(held: &mut Option<(File, usize)>, lock_path: &Path, mut file: File) -> Result<HomeLock> {
+    file.set_len(0)
+        .and_then(|()| file.rewind())
+        .and_then(|()| write!(file, "{}", std::process::id()))
+        .with_context(|| format!("Failed to write {}", lock_path.display()))?;
+    *held = Some((file, 1));
+    Ok(HomeLock(()))
+}
+
+fn timeout() -> Result<Duration> {
+    match std::env::var(TIMEOUT_ENV) {
+        Ok(value) if !value.trim().is_empty() => {
+            value.trim().parse().map(Duration::from_secs).map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid {} '{}': expected a number of seconds",
+                    TIMEOUT_ENV,
+                    value
+                )
+            })
+        }
+        _ => Ok(DEFAULT_TIMEOUT),
+    }
+}
+
+/// The PID recorded in the lock file by the process holding the lock.
+fn holder(file: &mut File) -> String {
+    let mut pid = String::new();
+    let _ = file.rewind().and_then(|()| file.read_to_string(&mut pid));
+    match pid.trim() {
+        "" => "unknown".to_string(),
+        pid => pid.to_string(),
+    }
+}
+
+/// Take the lock without blocking.  Returns false if another process holds it.
+#[cfg(unix)]
+fn try_lock(file: &File) -> Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: flock only operates on the descriptor, which `file` keeps open.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = std::io::Error::last_os_error();
+    if error.kind() == std::io::ErrorKind::WouldBlock {
+        return Ok(false);
+    }
+    Err(error).context("Failed to lock the nockup home directory")
+}
+
+/// nockup is only released for Unix platforms; elsewhere it runs unlocked.
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> Result<bool> {
+    Ok(true)
+}
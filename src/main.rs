@@ -1,29 +1,94 @@
 use std::process;
 
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 
+mod channel;
 mod cli;
 mod commands;
+mod config;
+mod download;
+mod generation;
+mod github;
 mod lib_manager;
+mod lock;
+mod manifest;
+mod pgp;
+mod proxy;
+mod receipt;
+mod shell;
+mod status;
+mod templates;
+mod toolchain;
 mod version;
 
 use cli::*;
 
 #[tokio::main]
 async fn main() {
+    // When invoked through a hoon/hoonc proxy, run the selected toolchain's binary.
+    if let Some(binary) = proxy::invoked_as() {
+        let args = std::env::args_os().skip(1).collect();
+        match proxy::run(binary, args).await {
+            Ok(code) => process::exit(code),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    clap_complete::CompleteEnv::with_factory(Cli::command)
+        .var(commands::completions::COMPLETE_ENV)
+        .complete();
+
     let cli = Cli::parse();
 
+    // Commands that change ~/.nockup hold its lock until they finish.
+    let _lock = match &cli.command {
+        Some(command) if command.mutates_home() => match lock::acquire().await {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
     let result = match cli.command {
         None => {
             // No subcommand provided - show version info
             version::show_version_info().await
         }
-        Some(Commands::Install) => commands::install::run().await,
+        Some(Commands::Install {
+            from,
+            profile,
+            templates_ref,
+            no_modify_path,
+        }) => commands::install::run(from, profile, templates_ref, !no_modify_path).await,
         Some(Commands::Init { name }) => commands::init::run(name).await,
-        Some(Commands::Update) => commands::update::run().await,
+        Some(Commands::Update { from, check: false }) => commands::update::run(from).await,
+        Some(Commands::Update { from, check: true }) => match commands::update::check(from).await {
+            Ok(code) => process::exit(code),
+            Err(e) => Err(e),
+        },
+        Some(Commands::Rollback) => commands::rollback::run().await,
         Some(Commands::Build { project }) => commands::build::run(project).await,
         Some(Commands::Run { project, args }) => commands::run::run(project, args).await,
         Some(Commands::Channel { action }) => commands::channel::run(action).await,
+        Some(Commands::Toolchain { action }) => commands::toolchain::run(action).await,
+        Some(Commands::Target { action }) => commands::target::run(action).await,
+        Some(Commands::Config { action }) => commands::config::run(action).await,
+        Some(Commands::SelfCmd { action }) => commands::self_update::run(action).await,
+        Some(Commands::Bundle { action }) => commands::bundle::run(action).await,
+        Some(Commands::Doctor { fix }) => commands::doctor::run(fix).await,
+        Some(Commands::Verify) => commands::verify::run(),
+        Some(Commands::Show { json }) => commands::show::run(json),
+        Some(Commands::Completions { shell }) => commands::completions::run(shell),
+        Some(Commands::Exec { command }) => match commands::exec::run(command).await {
+            Ok(code) => process::exit(code),
+            Err(e) => Err(e),
+        },
     };
 
     if let Err(e) = result {
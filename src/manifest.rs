@@ -0,0 +1,158 @@
+//! Channel manifests, as published with each release and collated by
+//! `scripts/collate-manifests.sh`.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// The manifest format this version of nockup understands.
+pub const MANIFEST_VERSION: &str = "1";
+
+/// Profile installed when none is configured.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Component that packages belong to when they do not list any.
+const DEFAULT_COMPONENT: &str = "core";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChannelManifest {
+    #[serde(rename = "manifest-version")]
+    pub manifest_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Nockchain commit the release was built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_tag: Option<String>,
+    #[serde(default)]
+    pub pkg: BTreeMap<String, Package>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Package {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Components this package belongs to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub target: BTreeMap<String, Target>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Target {
+    #[serde(default = "default_available")]
+    pub available: bool,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub hash_blake3: String,
+    #[serde(default)]
+    pub hash_sha1: String,
+}
+
+fn default_available() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Profile {
+    pub components: Vec<String>,
+}
+
+impl ChannelManifest {
+    pub fn parse(content: &str) -> Result<Self> {
+        let manifest: ChannelManifest =
+            toml::from_str(content).context("Failed to parse channel manifest")?;
+        if manifest.manifest_version != MANIFEST_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported channel manifest version '{}' (this nockup supports version {}); run 'nockup self update'",
+                manifest.manifest_version,
+                MANIFEST_VERSION
+            ));
+        }
+        Ok(manifest)
+    }
+
+    /// Packages to install for `profile`: those belonging to any component the
+    /// profile lists.  Manifests without profiles install every package.
+    pub fn profile_packages(&self, profile: &str) -> Result<Vec<&str>> {
+        if self.profiles.is_empty() {
+            return Ok(self.pkg.keys().map(String::as_str).collect());
+        }
+        let profile_components = &self
+            .profiles
+            .get(profile)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown profile '{}' (available: {})",
+                    profile,
+                    self.profiles
+                        .keys()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?
+            .components;
+        Ok(self
+            .pkg
+            .iter()
+            .filter(|(_, package)| {
+                package
+                    .components()
+                    .any(|component| profile_components.iter().any(|wanted| wanted == component))
+            })
+            .map(|(name, _)| name.as_str())
+            .collect())
+    }
+
+    /// The build of `package` for `architecture`.
+    pub fn target(&self, package: &str, architecture: &str) -> Result<&Target> {
+        let target = self
+            .pkg
+            .get(package)
+            .ok_or_else(|| anyhow::anyhow!("Channel manifest has no package '{}'", package))?
+            .target
+            .get(architecture)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Channel manifest has no build of '{}' for {}",
+                    package,
+                    architecture
+                )
+            })?;
+        if target.available
+            && (target.url.is_empty()
+                || target.hash_blake3.is_empty()
+                || target.hash_sha1.is_empty())
+        {
+            return Err(anyhow::anyhow!(
+                "Channel manifest entry for '{}' on {} is missing its url or hashes",
+                package,
+                architecture
+            ));
+        }
+        Ok(target)
+    }
+}
+
+impl Package {
+    fn components(&self) -> impl Iterator<Item = &str> {
+        let defaults: &[&str] = if self.components.is_empty() {
+            &[DEFAULT_COMPONENT]
+        } else {
+            &[]
+        };
+        self.components
+            .iter()
+            .map(String::as_str)
+            .chain(defaults.iter().copied())
+    }
+}
@@ -0,0 +1,396 @@
+//! Verification of detached OpenPGP signatures over release archives.
+//!
+//! Parsing and signature cryptography come from the `pgp` crate.  This module
+//! only decides which keys to trust: a key counts once its owner's
+//! self-signature verifies, and its signing capability and expiry come from
+//! the newest such signature, so they are data its owner signed.
+
+use std::io::Read;
+
+use chrono::Utc;
+use pgp::composed::{Deserializable, DetachedSignature, SignedPublicKey};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::packet::{PublicKey, PublicSubkey, Signature, SignatureType};
+use pgp::types::{KeyDetails, PublicKeyTrait, Tag};
+use thiserror::Error;
+
+use crate::config::NockupConfig;
+
+/// The Zorp release signing key, trusted unless disabled in `config.toml`.
+pub const ZORP_KEY: &str = include_str!("../zorp-gpg-key.pub");
+
+/// Environment variable listing extra trusted key files, separated like `PATH`.
+pub const TRUSTED_KEYS_ENV: &str = "NOCKUP_TRUSTED_KEYS";
+
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    #[error("bad signature from key {0}")]
+    BadSignature(String),
+    #[error("signature made by unknown key {0}")]
+    UnknownKey(String),
+    #[error("signature made by key {key} after it expired on {}", format_time(*.expired))]
+    SignedAfterExpiry { key: String, expired: u64 },
+    #[error("signing key {key} expired on {}", format_time(*.expired))]
+    ExpiredKey { key: String, expired: u64 },
+    #[error("signature expired on {}", format_time(*.0))]
+    ExpiredSignature(u64),
+    #[error("signature made by revoked key {0}")]
+    RevokedKey(String),
+    #[error("key {0} has no valid self-signature")]
+    UncertifiedKey(String),
+    #[error("signature uses the {0} digest, which is no longer accepted")]
+    WeakDigest(&'static str),
+    #[error("malformed OpenPGP data: {0}")]
+    Malformed(String),
+    #[error("failed to read signed data: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<pgp::errors::Error> for SignatureError {
+    fn from(error: pgp::errors::Error) -> Self {
+        match error {
+            pgp::errors::Error::IO { source, .. } => SignatureError::Io(source),
+            other => SignatureError::Malformed(other.to_string()),
+        }
+    }
+}
+
+/// Format a Unix timestamp as a date.
+pub fn format_time(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| secs.to_string())
+}
+
+/// A signature that checked out, and the key that made it.
+#[derive(Debug)]
+pub struct VerifiedSignature {
+    /// Fingerprint of the certificate's primary key, in uppercase hex.
+    pub fingerprint: String,
+    pub user_id: Option<String>,
+    pub created: u64,
+}
+
+/// A set of trusted certificates.
+#[derive(Debug, Default)]
+pub struct Keyring {
+    certificates: Vec<Certificate>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Keyring::default()
+    }
+
+    /// Add every certificate in `data`, which may be ASCII-armored or binary.
+    /// Fails if any primary key lacks a valid self-signature.
+    pub fn add_keys(&mut self, data: &[u8]) -> Result<(), SignatureError> {
+        let (keys, _) = SignedPublicKey::from_reader_many(data)?;
+        let mut certificates = Vec::new();
+        for key in keys {
+            certificates.push(Certificate::new(&key?)?);
+        }
+        if certificates.is_empty() {
+            return Err(SignatureError::Malformed(
+                "no public keys found".to_string(),
+            ));
+        }
+        self.certificates.extend(certificates);
+        Ok(())
+    }
+
+    /// Verify the detached `signature` over the contents of `data`.
+    pub fn verify(
+        &self,
+        signature: &[u8],
+        data: impl Read,
+    ) -> Result<VerifiedSignature, SignatureError> {
+        let (DetachedSignature { signature }, _) =
+            DetachedSignature::from_reader_single(signature)?;
+        if signature.typ() != Some(SignatureType::Binary) {
+            return Err(SignatureError::Malformed(format!(
+                "unsupported signature type {:?}",
+                signature.typ()
+            )));
+        }
+        if let Some(name) = signature.hash_alg().and_then(weak_digest) {
+            return Err(SignatureError::WeakDigest(name));
+        }
+
+        let (certificate, key) = self
+            .find_key(&signature)
+            .ok_or_else(|| SignatureError::UnknownKey(issuer_name(&signature)))?;
+        let key_name = hex::encode_upper(key.key.key_id());
+        if key.revoked || certificate.primary.revoked {
+            return Err(SignatureError::RevokedKey(key_name));
+        }
+
+        key.key.verify(&signature, data).map_err(|e| match e {
+            pgp::errors::Error::IO { source, .. } => SignatureError::Io(source),
+            _ => SignatureError::BadSignature(key_name.clone()),
+        })?;
+
+        let created = signature
+            .created()
+            .map(|time| time.timestamp() as u64)
+            .ok_or_else(|| {
+                SignatureError::Malformed("signature has no creation time".to_string())
+            })?;
+        let now = Utc::now().timestamp() as u64;
+        if let Some(expired) = key
+            .expires
+            .into_iter()
+            .chain(certificate.primary.expires)
+            .min()
+        {
+            if created >= expired {
+                return Err(SignatureError::SignedAfterExpiry {
+                    key: key_name,
+                    expired,
+                });
+            }
+            if now >= expired {
+                return Err(SignatureError::ExpiredKey {
+                    key: key_name,
+                    expired,
+                });
+            }
+        }
+        if let Some(lifetime) = signature.signature_expiration_time() {
+            let expired = created + lifetime.num_seconds().max(0) as u64;
+            if expired > created && now >= expired {
+                return Err(SignatureError::ExpiredSignature(expired));
+            }
+        }
+
+        Ok(VerifiedSignature {
+            fingerprint: hex::encode_upper(certificate.primary.key.fingerprint()),
+            user_id: certificate.user_id.clone(),
+            created,
+        })
+    }
+
+    fn find_key(&self, signature: &Signature) -> Option<(&Certificate, &ValidKey)> {
+        self.certificates.iter().find_map(|certificate| {
+            std::iter::once(&certificate.primary)
+                .chain(&certificate.subkeys)
+                .filter(|key| key.can_sign)
+                .find(|key| key.key.issued(signature))
+                .map(|key| (certificate, key))
+        })
+    }
+}
+
+/// Build the keyring used to verify release archives: the bundled Zorp key
+/// (unless `[signing] bundled_key = false`), key files listed under
+/// `[signing] keys` in `config.toml`, and files named in `NOCKUP_TRUSTED_KEYS`.
+pub fn trusted_keyring() -> anyhow::Result<Keyring> {
+    let signing = NockupConfig::load().unwrap_or_default().signing;
+
+    let mut keyring = Keyring::new();
+    if signing.bundled_key.unwrap_or(true) {
+        keyring
+            .add_keys(ZORP_KEY.as_bytes())
+            .map_err(|e| anyhow::anyhow!("Failed to load bundled Zorp key: {}", e))?;
+    }
+
+    let mut key_files = signing.keys;
+    if let Some(paths) = std::env::var_os(TRUSTED_KEYS_ENV) {
+        key_files.extend(std::env::split_paths(&paths).filter(|path| !path.as_os_str().is_empty()));
+    }
+    for key_file in key_files {
+        let data = std::fs::read(&key_file).map_err(|e| {
+            anyhow::anyhow!("Failed to read trusted key {}: {}", key_file.display(), e)
+        })?;
+        keyring.add_keys(&data).map_err(|e| {
+            anyhow::anyhow!("Failed to load trusted key {}: {}", key_file.display(), e)
+        })?;
+    }
+
+    if keyring.certificates.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No trusted signing keys configured; set [signing] keys in config.toml"
+        ));
+    }
+    Ok(keyring)
+}
+
+/// Digests too weak to accept over release data, by display name.
+fn weak_digest(algorithm: HashAlgorithm) -> Option<&'static str> {
+    match algorithm {
+        HashAlgorithm::Md5 => Some("MD5"),
+        HashAlgorithm::Sha1 => Some("SHA-1"),
+        HashAlgorithm::Ripemd160 => Some("RIPEMD-160"),
+        _ => None,
+    }
+}
+
+fn issuer_name(signature: &Signature) -> String {
+    if let Some(fingerprint) = signature.issuer_fingerprint().first() {
+        hex::encode_upper(fingerprint)
+    } else if let Some(key_id) = signature.issuer().first() {
+        hex::encode_upper(key_id)
+    } else {
+        "(no issuer)".to_string()
+    }
+}
+
+/// Seconds since the epoch at which a key created at `created` expires, given
+/// the key expiration time from its self-signature.  Zero means never.
+fn expiry(created: u64, signature: &Signature) -> Option<u64> {
+    let lifetime = signature.key_expiration_time()?.num_seconds();
+    (lifetime > 0).then(|| created + lifetime as u64)
+}
+
+fn created(signature: &Signature) -> i64 {
+    signature
+        .created()
+        .map(|time| time.timestamp())
+        .unwrap_or_default()
+}
+
+#[derive(Debug)]
+enum SigningKey {
+    Primary(PublicKey),
+    Subkey(PublicSubkey),
+}
+
+impl SigningKey {
+    fn key_id(&self) -> pgp::types::KeyId {
+        match self {
+            SigningKey::Primary(key) => key.key_id(),
+            SigningKey::Subkey(key) => key.key_id(),
+        }
+    }
+
+    fn fingerprint(&self) -> pgp::types::Fingerprint {
+        match self {
+            SigningKey::Primary(key) => key.fingerprint(),
+            SigningKey::Subkey(key) => key.fingerprint(),
+        }
+    }
+
+    fn issued(&self, signature: &Signature) -> bool {
+        let fingerprint = self.fingerprint();
+        let key_id = self.key_id();
+        signature.issuer_fingerprint().contains(&&fingerprint)
+            || signature.issuer().contains(&&key_id)
+    }
+
+    fn verify(&self, signature: &Signature, data: impl Read) -> pgp::errors::Result<()> {
+        match self {
+            SigningKey::Primary(key) => signature.verify(key, data),
+            SigningKey::Subkey(key) => signature.verify(key, data),
+        }
+    }
+}
+
+/// A key whose self-signature verified, with the properties it grants.
+#[derive(Debug)]
+struct ValidKey {
+    key: SigningKey,
+    can_sign: bool,
+    /// Seconds since the epoch at which the key expires.
+    expires: Option<u64>,
+    revoked: bool,
+}
+
+#[derive(Debug)]
+struct Certificate {
+    primary: ValidKey,
+    subkeys: Vec<ValidKey>,
+    user_id: Option<String>,
+}
+
+impl Certificate {
+    fn new(certificate: &SignedPublicKey) -> Result<Self, SignatureError> {
+        let primary = &certificate.primary_key;
+        let details = &certificate.details;
+        let created_at = primary.created_at().timestamp() as u64;
+
+        // Self-signatures: direct-key signatures and certifications of the
+        // key's own user IDs.  Anything that does not verify is ignored.
+        let direct = details
+            .direct_signatures
+            .iter()
+            .filter(|signature| signature.verify_key(primary).is_ok())
+            .map(|signature| (signature, None));
+        let certifications = details.users.iter().flat_map(|user| {
+            user.signatures
+                .iter()
+                .filter(|signature| {
+                    signature
+                        .verify_certification(primary, Tag::UserId, &user.id)
+                        .is_ok()
+                })
+                .map(move |signature| (signature, Some(user)))
+        });
+        let self_signatures: Vec<_> = direct.chain(certifications).collect();
+        let Some(&(newest, _)) = self_signatures
+            .iter()
+            .max_by_key(|(signature, _)| created(signature))
+        else {
+            return Err(SignatureError::UncertifiedKey(hex::encode_upper(
+                primary.key_id(),
+            )));
+        };
+
+        let user_id = self_signatures
+            .iter()
+            .filter_map(|(signature, user)| user.map(|user| (signature, user)))
+            .max_by_key(|(signature, _)| (signature.is_primary(), created(signature)))
+            .and_then(|(_, user)| user.id.as_str().map(str::to_string));
+        let revoked = details
+            .revocation_signatures
+            .iter()
+            .any(|signature| signature.verify_key(primary).is_ok());
+
+        let subkeys = certificate
+            .public_subkeys
+            .iter()
+            .filter_map(|subkey| {
+                let binding = subkey
+                    .signatures
+                    .iter()
+                    .filter(|signature| signature.typ() == Some(SignatureType::SubkeyBinding))
+                    .filter(|signature| {
+                        signature
+                            .verify_subkey_binding(primary, &subkey.key)
+                            .is_ok()
+                    })
+                    .max_by_key(|signature| created(signature))?;
+                // A signing subkey must also sign the primary key back, so
+                // nobody can claim someone else's subkey as their own.
+                let can_sign = binding.key_flags().sign()
+                    && binding.embedded_signature().is_some_and(|backsig| {
+                        backsig
+                            .verify_primary_key_binding(&subkey.key, primary)
+                            .is_ok()
+                    });
+                let revoked = subkey.signatures.iter().any(|signature| {
+                    signature.typ() == Some(SignatureType::SubkeyRevocation)
+                        && signature
+                            .verify_subkey_binding(primary, &subkey.key)
+                            .is_ok()
+                });
+                Some(ValidKey {
+                    expires: expiry(subkey.key.created_at().timestamp() as u64, binding),
+                    key: SigningKey::Subkey(subkey.key.clone()),
+                    can_sign,
+                    revoked,
+                })
+            })
+            .collect();
+
+        Ok(Certificate {
+            primary: ValidKey {
+                key: SigningKey::Primary(primary.clone()),
+                can_sign: newest.key_flags().sign(),
+                expires: expiry(created_at, newest),
+                revoked,
+            },
+            subkeys,
+            user_id,
+        })
+    }
+}
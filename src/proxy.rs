@@ -0,0 +1,112 @@
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::config;
+use crate::toolchain::{self, TOOLCHAIN_BINARIES, TOOLCHAIN_ENV};
+
+/// If nockup was invoked through one of the proxies in `~/.nockup/bin` (i.e.
+/// as `hoon` or `hoonc`), return that binary's name.
+pub fn invoked_as() -> Option<&'static str> {
+    let arg0 = std::env::args_os().next()?;
+    let stem = Path::new(&arg0).file_stem()?.to_str()?;
+    TOOLCHAIN_BINARIES
+        .into_iter()
+        .find(|&binary| binary == stem)
+}
+
+/// Run `binary` from the selected toolchain, forwarding `args`.  A leading
+/// `+toolchain` argument overrides the toolchain for this invocation only.
+pub async fn run(binary: &str, args: Vec<OsString>) -> Result<i32> {
+    let (override_name, args) = split_toolchain_override(args);
+    let (name, bin_dir) = toolchain::select_toolchain(override_name.as_deref()).await?;
+    let program = bin_dir.join(binary);
+    if !program.exists() {
+        return Err(anyhow::anyhow!(
+            "'{}' is not provided by toolchain '{}'",
+            binary,
+            name
+        ));
+    }
+    exec(program.as_os_str(), &args, &name, &bin_dir)
+}
+
+/// Split a leading `+toolchain` argument off `args`.
+pub fn split_toolchain_override(mut args: Vec<OsString>) -> (Option<String>, Vec<OsString>) {
+    let override_name = args
+        .first()
+        .and_then(|arg| arg.to_str())
+        .and_then(|arg| arg.strip_prefix('+'))
+        .filter(|name| !name.is_empty())
+        .map(String::from);
+    if override_name.is_some() {
+        args.remove(0);
+    }
+    (override_name, args)
+}
+
+/// Run `program` with the toolchain's `bin` directory first on `PATH` and
+/// `NOCKUP_TOOLCHAIN` set, so nested `hoon`/`hoonc` calls resolve to the same
+/// toolchain.  On Unix this replaces the current process.
+pub fn exec(program: &OsStr, args: &[OsString], name: &str, bin_dir: &Path) -> Result<i32> {
+    let mut command = std::process::Command::new(program);
+    command
+        .args(args)
+        .env("PATH", toolchain::prepended_path(bin_dir)?)
+        .env(TOOLCHAIN_ENV, name);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let error = command.exec();
+        Err(anyhow::anyhow!(
+            "Failed to execute '{}': {}",
+            program.to_string_lossy(),
+            error
+        ))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = command
+            .status()
+            .with_context(|| format!("Failed to execute '{}'", program.to_string_lossy()))?;
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+/// Install `hoon` and `hoonc` proxies into `~/.nockup/bin`.  Each proxy is a
+/// hard link to (or, failing that, a copy of) the installed nockup executable,
+/// which dispatches on the name it was invoked as.  A nockup run from
+/// elsewhere, e.g. a development build, links the proxies to itself instead.
+///
+/// Each proxy is written under a temporary name and renamed over the old one,
+/// so an existing proxy is never missing, even if installing fails.
+pub fn install_proxies() -> Result<()> {
+    let bin_dir = config::nockup_home()?.join("bin");
+    std::fs::create_dir_all(&bin_dir)?;
+    // Not the running executable: `nockup update` may just have replaced it,
+    // leaving the running process with no path to link to.
+    let installed = bin_dir.join(format!("nockup{}", std::env::consts::EXE_SUFFIX));
+    let nockup = if installed.exists() {
+        installed
+    } else {
+        std::env::current_exe().context("Failed to locate the nockup executable")?
+    };
+
+    for binary in TOOLCHAIN_BINARIES {
+        let proxy = bin_dir.join(format!("{}{}", binary, std::env::consts::EXE_SUFFIX));
+        let temp = bin_dir.join(format!(".{}.tmp", binary));
+        let _ = std::fs::remove_file(&temp);
+        if std::fs::hard_link(&nockup, &temp).is_err() {
+            std::fs::copy(&nockup, &temp)
+                .with_context(|| format!("Failed to install proxy {}", proxy.display()))?;
+        }
+        if let Err(e) = std::fs::rename(&temp, &proxy) {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("Failed to install proxy {}", proxy.display()));
+        }
+    }
+    Ok(())
+}
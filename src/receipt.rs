@@ -0,0 +1,149 @@
+//! Install receipts.
+//!
+//! Each directory binaries are installed into (a toolchain, a target added
+//! with `nockup target add`, and `~/.nockup` itself for the nockup binary)
+//! keeps a `receipt.toml` next to its `bin` directory.  It records where each
+//! binary came from and what it hashed to when installed, so `nockup verify`
+//! can later tell whether it has been modified.  The library cache keeps one
+//! too, recording the repository and commit each cached library came from.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::download::Hashes;
+
+pub const RECEIPT_FILE: &str = "receipt.toml";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Receipt {
+    #[serde(default)]
+    pub components: BTreeMap<String, ComponentReceipt>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ComponentReceipt {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Nockchain commit of the channel manifest the binary was installed from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub architecture: String,
+    /// URL of the release archive.
+    pub url: String,
+    pub archive_blake3: String,
+    pub archive_sha1: String,
+    /// Hashes of the installed binary.
+    pub blake3: String,
+    pub sha1: String,
+    /// Fingerprint of the key that signed the archive, if it was signed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// When the binary was installed, in RFC 3339 format.
+    pub installed: String,
+}
+
+impl ComponentReceipt {
+    /// Whether `hashes` are those of the binary as installed.
+    pub fn matches(&self, hashes: &Hashes) -> bool {
+        hashes.blake3.eq_ignore_ascii_case(&self.blake3)
+            && hashes.sha1.eq_ignore_ascii_case(&self.sha1)
+    }
+}
+
+/// The current time, as recorded in receipts.
+pub fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+impl Receipt {
+    /// The receipt in `dir`, or `None` if its binaries were installed before
+    /// receipts were recorded.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let receipt_path = dir.join(RECEIPT_FILE);
+        if !receipt_path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&receipt_path)
+            .with_context(|| format!("Failed to read {}", receipt_path.display()))?;
+        let receipt = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", receipt_path.display()))?;
+        Ok(Some(receipt))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let receipt_path = dir.join(RECEIPT_FILE);
+        fs::write(&receipt_path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", receipt_path.display()))
+    }
+
+    /// Record `component` in the receipt in `dir`, keeping the others.
+    pub fn add(dir: &Path, name: &str, component: ComponentReceipt) -> Result<()> {
+        let mut receipt = Receipt::load(dir)?.unwrap_or_default();
+        receipt.components.insert(name.to_string(), component);
+        receipt.save(dir)
+    }
+
+    /// Copy the record of `name` from the receipt in `from` to the one in
+    /// `to`, as the binary it describes moves between them.  If `from` has no
+    /// record of it, any in `to` is dropped.
+    pub fn copy_component(from: &Path, to: &Path, name: &str) -> Result<()> {
+        let component =
+            Receipt::load(from)?.and_then(|mut receipt| receipt.components.remove(name));
+        match (component, Receipt::load(to)?) {
+            (Some(component), receipt) => {
+                let mut receipt = receipt.unwrap_or_default();
+                receipt.components.insert(name.to_string(), component);
+                receipt.save(to)
+            }
+            (None, Some(mut receipt)) => {
+                receipt.components.remove(name);
+                receipt.save(to)
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The receipt in `~/.nockup/library_cache`, keyed by cache directory name.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LibraryReceipts {
+    #[serde(default)]
+    pub libraries: BTreeMap<String, LibraryReceipt>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LibraryReceipt {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Commit checked out in the cache.
+    pub commit: String,
+    /// When the library was cloned, in RFC 3339 format.
+    pub fetched: String,
+}
+
+impl LibraryReceipts {
+    pub fn load(cache_dir: &Path) -> Result<Self> {
+        let receipt_path = cache_dir.join(RECEIPT_FILE);
+        if !receipt_path.exists() {
+            return Ok(LibraryReceipts::default());
+        }
+        let content = fs::read_to_string(&receipt_path)
+            .with_context(|| format!("Failed to read {}", receipt_path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", receipt_path.display()))
+    }
+
+    /// Record the library cloned into `cache_dir/name`.
+    pub fn add(cache_dir: &Path, name: &str, library: LibraryReceipt) -> Result<()> {
+        let mut receipts = LibraryReceipts::load(cache_dir)?;
+        receipts.libraries.insert(name.to_string(), library);
+        let receipt_path = cache_dir.join(RECEIPT_FILE);
+        fs::write(&receipt_path, toml::to_string(&receipts)?)
+            .with_context(|| format!("Failed to write {}", receipt_path.display()))
+    }
+}
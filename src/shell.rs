@@ -0,0 +1,265 @@
+//! Putting `~/.nockup/bin` on `PATH`.
+//!
+//! nockup writes an env script for each supported shell into `~/.nockup`
+//! (`env` for POSIX shells, `env.fish` and `env.nu`) and adds a single marked
+//! line sourcing the current shell's script to its startup files.  The scripts
+//! only prepend the directory if it is missing, so sourcing them repeatedly is
+//! harmless, and removing the marked lines undoes the change.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Comment written above the line nockup adds to shell startup files.
+pub const SHELL_RC_MARKER: &str = "# Added by nockup installer";
+
+/// POSIX startup files.  For a POSIX shell `.profile` is always updated; the
+/// others only if they exist or belong to the current shell.
+const POSIX_RC_FILES: [&str; 4] = [".profile", ".bashrc", ".bash_profile", ".zshrc"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    Posix,
+    Fish,
+    Nu,
+}
+
+impl Shell {
+    /// The kind of shell named by `$SHELL`, defaulting to POSIX.
+    fn current() -> Shell {
+        match current_shell_name().as_str() {
+            "fish" => Shell::Fish,
+            "nu" => Shell::Nu,
+            _ => Shell::Posix,
+        }
+    }
+
+    fn env_script(self) -> &'static str {
+        match self {
+            Shell::Posix => "env",
+            Shell::Fish => "env.fish",
+            Shell::Nu => "env.nu",
+        }
+    }
+
+    /// The line that sources this shell's env script from `cache_dir`.
+    fn source_line(self, cache_dir: &Path) -> String {
+        let script = self.escape(&cache_dir.join(self.env_script()));
+        match self {
+            Shell::Posix => format!(". \"{}\"", script),
+            Shell::Fish | Shell::Nu => format!("source \"{}\"", script),
+        }
+    }
+
+    /// `path` escaped for use inside a double-quoted string in this shell, so
+    /// that spaces, quotes and `$` in the nockup home are taken literally.
+    fn escape(self, path: &Path) -> String {
+        let special: &[char] = match self {
+            Shell::Posix => &['\\', '"', '$', '`'],
+            Shell::Fish => &['\\', '"', '$'],
+            Shell::Nu => &['\\', '"'],
+        };
+        let mut escaped = String::new();
+        for c in path.display().to_string().chars() {
+            if special.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+}
+
+/// The file name of `$SHELL`, e.g. `bash`.
+fn current_shell_name() -> String {
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    Path::new(&shell)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The line `nockup install` suggests for putting nockup on `PATH` in the
+/// current session.
+pub fn posix_source_line(cache_dir: &Path) -> String {
+    Shell::Posix.source_line(cache_dir)
+}
+
+/// Write the env scripts that put `cache_dir/bin` on `PATH`.
+pub fn write_env_scripts(cache_dir: &Path) -> Result<()> {
+    let bin_dir = cache_dir.join("bin");
+    let scripts = [Shell::Posix, Shell::Fish, Shell::Nu].map(|shell| {
+        let bin = shell.escape(&bin_dir);
+        let script = match shell {
+            Shell::Posix => format!(
+                "#!/bin/sh\n# nockup shell setup\ncase \":${{PATH}}:\" in\n    *:\"{bin}\":*)\n        ;;\n    *)\n        export PATH=\"{bin}:$PATH\"\n        ;;\nesac\n"
+            ),
+            Shell::Fish => format!(
+                "# nockup shell setup\nif not contains \"{bin}\" $PATH\n    set -gx PATH \"{bin}\" $PATH\nend\n"
+            ),
+            Shell::Nu => format!(
+                "# nockup shell setup\n$env.PATH = ($env.PATH | split row (char esep) | where $it != \"{bin}\" | prepend \"{bin}\")\n"
+            ),
+        };
+        (shell, script)
+    });
+    for (shell, script) in scripts {
+        let path = cache_dir.join(shell.env_script());
+        fs::write(&path, script).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Every startup file nockup may have modified, and the shell it belongs to.
+fn rc_files() -> Result<Vec<(PathBuf, Shell)>> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+    let mut files: Vec<(PathBuf, Shell)> = POSIX_RC_FILES
+        .iter()
+        .map(|file| (home.join(file), Shell::Posix))
+        .collect();
+    files.push((home.join(".config/fish/conf.d/nockup.fish"), Shell::Fish));
+    if let Some(config_dir) = dirs::config_dir() {
+        files.push((config_dir.join("nushell/config.nu"), Shell::Nu));
+    }
+    Ok(files)
+}
+
+/// Whether `rc_path` should be updated.  Only the current shell's startup
+/// files are:  for a POSIX shell, those that exist, belong to it, or are
+/// `.profile`, which login shells read.
+fn wants_update(rc_path: &Path, shell: Shell) -> bool {
+    if shell != Shell::current() {
+        return false;
+    }
+    let current = current_shell_name();
+    let file_name = rc_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    match shell {
+        Shell::Posix => {
+            file_name == ".profile"
+                || rc_path.exists()
+                || (current == "bash" && file_name == ".bashrc")
+                || (current == "zsh" && file_name == ".zshrc")
+        }
+        Shell::Fish | Shell::Nu => true,
+    }
+}
+
+/// Write the env scripts and add a line sourcing the current shell's to its
+/// startup files, replacing any `export PATH` line written by older versions.
+/// Returns the files changed.
+pub fn modify_path(cache_dir: &Path) -> Result<Vec<PathBuf>> {
+    write_env_scripts(cache_dir)?;
+
+    let mut changed = Vec::new();
+    for (rc_path, shell) in rc_files()? {
+        if !wants_update(&rc_path, shell) {
+            continue;
+        }
+        let contents = if rc_path.exists() {
+            fs::read_to_string(&rc_path)
+                .with_context(|| format!("Failed to read {}", rc_path.display()))?
+        } else {
+            String::new()
+        };
+
+        let source_line = shell.source_line(cache_dir);
+        let mut new_contents =
+            remove_entries(&contents, &legacy_entries(cache_dir)).unwrap_or(contents.clone());
+        if !new_contents.lines().any(|line| line.trim() == source_line) {
+            if !new_contents.is_empty() {
+                if !new_contents.ends_with('\n') {
+                    new_contents.push('\n');
+                }
+                new_contents.push('\n');
+            }
+            new_contents.push_str(&format!("{}\n{}\n", SHELL_RC_MARKER, source_line));
+        }
+        if new_contents == contents {
+            continue;
+        }
+
+        if let Some(parent) = rc_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&rc_path, new_contents)
+            .with_context(|| format!("Failed to write {}", rc_path.display()))?;
+        changed.push(rc_path);
+    }
+    Ok(changed)
+}
+
+/// Remove the lines nockup added to shell startup files, along with their
+/// marker comments.  Returns the files changed.
+pub fn restore_path(cache_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut changed = Vec::new();
+    for (rc_path, shell) in rc_files()? {
+        if !rc_path.exists() {
+            continue;
+        }
+        let contents = fs::read_to_string(&rc_path)
+            .with_context(|| format!("Failed to read {}", rc_path.display()))?;
+        let mut entries = legacy_entries(cache_dir);
+        entries.push(shell.source_line(cache_dir));
+        let Some(new_contents) = remove_entries(&contents, &entries) else {
+            continue;
+        };
+
+        // The fish file is nockup's own, so remove it rather than leave it empty.
+        if shell == Shell::Fish && new_contents.trim().is_empty() {
+            fs::remove_file(&rc_path)
+                .with_context(|| format!("Failed to remove {}", rc_path.display()))?;
+        } else {
+            fs::write(&rc_path, new_contents)
+                .with_context(|| format!("Failed to write {}", rc_path.display()))?;
+        }
+        changed.push(rc_path);
+    }
+    Ok(changed)
+}
+
+/// `export PATH` lines written to startup files by older versions of nockup
+/// and `install.sh`.
+fn legacy_entries(cache_dir: &Path) -> Vec<String> {
+    [cache_dir.join("bin"), PathBuf::from("$HOME/.nockup/bin")]
+        .iter()
+        .map(|bin_dir| format!("export PATH=\"{}:$PATH\"", bin_dir.display()))
+        .collect()
+}
+
+/// Remove `entries` from `contents`, along with the marker comment and blank
+/// line written above each.  Returns `None` if none were found.
+fn remove_entries(contents: &str, entries: &[String]) -> Option<String> {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut changed = false;
+    for line in contents.lines() {
+        if entries.iter().any(|entry| line.trim() == entry) {
+            changed = true;
+            if lines
+                .last()
+                .is_some_and(|last| last.trim() == SHELL_RC_MARKER)
+            {
+                lines.pop();
+                if lines.last().is_some_and(|last| last.trim().is_empty()) {
+                    lines.pop();
+                }
+            }
+            continue;
+        }
+        lines.push(line);
+    }
+    if !changed {
+        return None;
+    }
+
+    let mut new_contents = lines.join("\n");
+    if !new_contents.is_empty() && contents.ends_with('\n') {
+        new_contents.push('\n');
+    }
+    Some(new_contents)
+}
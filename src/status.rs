@@ -0,0 +1,141 @@
+//! `~/.nockup/status.toml`, recording what the last install or update put in
+//! place.  `nockup update --check` compares it against the latest channel
+//! manifest, and updates reuse binaries whose release archive is unchanged.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::download::hash_file;
+use crate::manifest::{ChannelManifest, Target};
+
+const STATUS_FILE: &str = "status.toml";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Status {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitStatus>,
+    /// Channel spec the installed build was taken from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Name of the installed toolchain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolchain: Option<String>,
+    #[serde(default)]
+    pub components: BTreeMap<String, ComponentStatus>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CommitStatus {
+    /// Nockchain commit of the installed build.
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ComponentStatus {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Blake3 hash of the release archive the binary came from.
+    pub archive_blake3: String,
+    pub path: PathBuf,
+    /// Blake3 hash of the installed binary.
+    pub blake3: String,
+}
+
+impl ComponentStatus {
+    /// Whether the binary at `path` is still the one that was installed.
+    pub fn is_intact(&self) -> bool {
+        hash_file(&self.path).is_ok_and(|hashes| hashes.blake3 == self.blake3)
+    }
+
+    /// Whether this is an intact install of `target`'s archive.
+    pub fn matches(&self, target: &Target) -> bool {
+        self.archive_blake3 == target.hash_blake3 && self.is_intact()
+    }
+
+    /// Short description for update reports, e.g. `0.4.0 (1a2b3c4d)`.
+    pub fn label(&self) -> String {
+        label(self.version.as_deref(), &self.archive_blake3)
+    }
+}
+
+/// Short description of a build for update reports.
+pub fn label(version: Option<&str>, archive_blake3: &str) -> String {
+    let hash = &archive_blake3[..archive_blake3.len().min(8)];
+    match version {
+        Some(version) => format!("{} ({})", version, hash),
+        None => hash.to_string(),
+    }
+}
+
+fn status_path() -> Result<PathBuf> {
+    Ok(config::nockup_home()?.join(STATUS_FILE))
+}
+
+impl Status {
+    /// The recorded status, or an empty one if nothing has been recorded.
+    pub fn load() -> Result<Self> {
+        let status_path = status_path()?;
+        if !status_path.exists() {
+            return Ok(Status::default());
+        }
+        let content = fs::read_to_string(&status_path)
+            .with_context(|| format!("Failed to read {}", status_path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", status_path.display()))
+    }
+
+    /// Record the binaries installed from `manifest`: those of `packages` found
+    /// at the path `installed_path` gives for each.
+    pub fn record(
+        manifest: &ChannelManifest,
+        channel: &str,
+        toolchain: &str,
+        architecture: &str,
+        packages: &[&str],
+        installed_path: impl Fn(&str) -> Result<PathBuf>,
+    ) -> Result<()> {
+        let mut components = BTreeMap::new();
+        for &package in packages {
+            let Ok(target) = manifest.target(package, architecture) else {
+                continue;
+            };
+            let path = installed_path(package)?;
+            if !target.available || !path.exists() {
+                continue;
+            }
+            components.insert(
+                package.to_string(),
+                ComponentStatus {
+                    version: manifest.pkg[package].version.clone(),
+                    archive_blake3: target.hash_blake3.clone(),
+                    blake3: hash_file(&path)?.blake3,
+                    path,
+                },
+            );
+        }
+
+        let status = Status {
+            commit: manifest.commit.clone().map(|id| CommitStatus { id }),
+            channel: Some(channel.to_string()),
+            toolchain: Some(toolchain.to_string()),
+            components,
+        };
+        fs::write(status_path()?, toml::to_string(&status)?)
+            .context("Failed to write status file")?;
+        Ok(())
+    }
+
+    /// An intact installed binary of `package` built from `target`'s archive,
+    /// which can be copied instead of downloading the archive again.
+    pub fn reusable(&self, package: &str, target: &Target) -> Option<&Path> {
+        self.components
+            .get(package)
+            .filter(|component| component.matches(target))
+            .map(|component| component.path.as_path())
+    }
+}
@@ -0,0 +1,416 @@
+//! Fetching a version of the templates repository.
+//!
+//! Templates are downloaded as the source archive GitHub publishes for each
+//! commit, so git need not be installed.  The requested ref is resolved to a
+//! commit through the GitHub API first, and the archive is checked against
+//! that commit by recomputing git's tree hash over its contents, so a
+//! truncated or substituted archive is rejected.  git remains a fallback for
+//! when the archive cannot be used, and is the only option for repositories
+//! that are not hosted on GitHub and have no archive mirror configured.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path};
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use tar::{Archive, EntryType};
+use tokio::process::Command;
+
+use crate::commands::common::{self, Mirrors};
+use crate::download::download_file;
+use crate::github;
+
+/// Directories of the templates repository that nockup installs.
+const TEMPLATE_DIRS: [&str; 2] = ["templates", "manifests"];
+
+/// The parts of a GitHub commit response needed to check an archive.
+#[derive(Deserialize)]
+struct CommitResponse {
+    sha: String,
+    commit: CommitDetails,
+}
+
+#[derive(Deserialize)]
+struct CommitDetails {
+    tree: TreeRef,
+}
+
+#[derive(Deserialize)]
+struct TreeRef {
+    sha: String,
+}
+
+/// Fetch `git_ref` (a branch, tag or full commit hash) of the templates
+/// repository and move its `templates` and `manifests` directories into
+/// `root`, replacing those already there.  Returns the commit fetched.
+pub async fn fetch(root: &Path, git_ref: &str) -> Result<String> {
+    let mirrors = Mirrors::load();
+    let git_installed = which::which("git").is_ok();
+    let (Some(api), Some(archive)) = (&mirrors.templates_api, &mirrors.templates_archive) else {
+        if !git_installed {
+            return Err(anyhow::anyhow!(
+                "Templates from {} can only be fetched with git, which is not installed",
+                mirrors.templates
+            ));
+        }
+        return fetch_with_git(root, &mirrors.templates, git_ref).await;
+    };
+
+    match fetch_archive(root, api, archive, git_ref).await {
+        Ok(commit) => Ok(commit),
+        Err(e) if git_installed => {
+            println!("{} {}; falling back to git", "⚠️".yellow(), e);
+            fetch_with_git(root, &mirrors.templates, git_ref).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Resolve `git_ref` of the templates repository to the commit it currently
+/// names.  A full commit hash names itself, so it is not looked up.
+pub async fn resolve(git_ref: &str) -> Result<String> {
+    if git_ref.len() == 40 && git_ref.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(git_ref.to_ascii_lowercase());
+    }
+    let mirrors = Mirrors::load();
+    let git_installed = which::which("git").is_ok();
+    let Some(api) = &mirrors.templates_api else {
+        if !git_installed {
+            return Err(anyhow::anyhow!(
+                "Templates from {} can only be checked with git, which is not installed",
+                mirrors.templates
+            ));
+        }
+        return resolve_with_git(&mirrors.templates, git_ref).await;
+    };
+
+    let commit: Result<CommitResponse> = github::get_json(
+        &format!("{}/commits/{}", api, git_ref),
+        &format!("templates commit for '{}'", git_ref),
+    )
+    .await;
+    match commit {
+        Ok(commit) => Ok(commit.sha),
+        Err(e) if git_installed => {
+            println!("{} {}; falling back to git", "⚠️".yellow(), e);
+            resolve_with_git(&mirrors.templates, git_ref).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn resolve_with_git(url: &str, git_ref: &str) -> Result<String> {
+    let output = run_git(&std::env::temp_dir(), &["ls-remote", url, git_ref])
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to look up templates '{}' in {}: {}",
+                git_ref,
+                url,
+                e
+            )
+        })?;
+    // Annotated tags are listed twice; the `^{}` line names the commit.
+    let refs: Vec<(&str, &str)> = output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect();
+    refs.iter()
+        .find(|(_, name)| name.ends_with("^{}"))
+        .or_else(|| refs.first())
+        .map(|(commit, _)| commit.to_string())
+        .ok_or_else(|| anyhow::anyhow!("Templates repository {} has no ref '{}'", url, git_ref))
+}
+
+async fn fetch_archive(root: &Path, api: &str, archive: &str, git_ref: &str) -> Result<String> {
+    let commit: CommitResponse = github::get_json(
+        &format!("{}/commits/{}", api, git_ref),
+        &format!("templates commit for '{}'", git_ref),
+    )
+    .await?;
+    let artifact = download_file(&format!("{}/{}.tar.gz", archive, commit.sha)).await?;
+
+    // Nothing is written until the whole archive has been checked.
+    let (tree, files) = read_archive(&artifact.path)?;
+    if tree != commit.commit.tree.sha {
+        return Err(anyhow::anyhow!(
+            "Templates archive for '{}' does not match commit {}: its tree hash is {}, expected {}",
+            git_ref,
+            &commit.sha[..commit.sha.len().min(7)],
+            tree,
+            commit.commit.tree.sha
+        ));
+    }
+
+    let temp_dir = staging_dir(root)?;
+    let result = files
+        .iter()
+        .try_for_each(|file| file.write(&temp_dir))
+        .and_then(|()| install(&temp_dir, root));
+    fs::remove_dir_all(&temp_dir).ok();
+    result.map(|()| commit.sha)
+}
+
+async fn fetch_with_git(root: &Path, url: &str, git_ref: &str) -> Result<String> {
+    let temp_dir = staging_dir(root)?;
+    let result = async {
+        run_git(&temp_dir, &["init", "-q"]).await?;
+        run_git(&temp_dir, &["fetch", "-q", "--depth=1", url, git_ref])
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to fetch templates '{}' from {}: {}",
+                    git_ref,
+                    url,
+                    e
+                )
+            })?;
+        run_git(&temp_dir, &["checkout", "-q", "FETCH_HEAD"]).await?;
+        let commit = run_git(&temp_dir, &["rev-parse", "HEAD"]).await?;
+        install(&temp_dir, root)?;
+        Ok(commit)
+    }
+    .await;
+    fs::remove_dir_all(&temp_dir).ok();
+    result
+}
+
+/// An empty directory in `root` to unpack a templates version into.
+fn staging_dir(root: &Path) -> Result<std::path::PathBuf> {
+    let temp_dir = root.join("temp_repo");
+    if temp_dir.exists() {
+        fs::remove_dir_all(&temp_dir)?;
+    }
+    fs::create_dir_all(&temp_dir)
+        .with_context(|| format!("Failed to create {}", temp_dir.display()))?;
+    Ok(temp_dir)
+}
+
+/// Move the `templates` and `manifests` directories unpacked in `src` into
+/// `root`.
+fn install(src: &Path, root: &Path) -> Result<()> {
+    for dir_name in TEMPLATE_DIRS {
+        if !src.join(dir_name).exists() {
+            return Err(anyhow::anyhow!(
+                "No '{}' directory found in the repository",
+                dir_name
+            ));
+        }
+    }
+    for dir_name in TEMPLATE_DIRS {
+        let src = src.join(dir_name);
+        let dst = root.join(dir_name);
+        if dst.exists() {
+            fs::remove_dir_all(&dst)
+                .with_context(|| format!("Failed to remove {}", dst.display()))?;
+        }
+        match fs::rename(&src, &dst) {
+            Ok(_) => {}
+            Err(e) if e.raw_os_error() == Some(66) => {
+                println!("{} Rename failed, copying instead...", "⚠️".yellow());
+                common::copy_dir_recursive(&src, &dst)?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Run git in `dir`, returning its trimmed output.
+async fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args[0],
+            stderr.trim().lines().last().unwrap_or("unknown error")
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// A file of the `templates` or `manifests` directory of a source archive.
+struct ArchiveFile {
+    /// Path components below the archive's top-level directory.
+    parts: Vec<String>,
+    mode: &'static str,
+    /// File contents, or a symlink's target.
+    content: Vec<u8>,
+}
+
+/// Read a source archive, returning the git tree hash of the whole archive
+/// and the files of its `templates` and `manifests` directories.  Archives in
+/// which a path appears twice, or lies beneath a file or symlink, are
+/// rejected, so the files returned are exactly those the hash covers.
+fn read_archive(archive_path: &Path) -> Result<(String, Vec<ArchiveFile>)> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("Failed to open {}", archive_path.display()))?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+    let mut tree = Tree::default();
+    let mut files = Vec::new();
+
+    for entry in archive
+        .entries()
+        .context("Failed to read templates archive")?
+    {
+        let mut entry = entry.context("Failed to read templates archive")?;
+        let path = entry.path()?.into_owned();
+        // Everything sits in one top-level directory named for the commit.
+        let parts = path
+            .components()
+            .skip(1)
+            .map(|component| match component {
+                Component::Normal(part) => Ok(part.to_string_lossy().into_owned()),
+                _ => Err(anyhow::anyhow!(
+                    "Templates archive contains an unsafe path: {}",
+                    path.display()
+                )),
+            })
+            .collect::<Result<Vec<String>>>()?;
+        if parts.is_empty() {
+            continue;
+        }
+
+        let (mode, content) = match entry.header().entry_type() {
+            EntryType::Regular => {
+                let executable = entry.header().mode()? & 0o111 != 0;
+                let mut content = Vec::new();
+                entry
+                    .read_to_end(&mut content)
+                    .with_context(|| format!("Failed to read {} from archive", path.display()))?;
+                (if executable { "100755" } else { "100644" }, content)
+            }
+            EntryType::Symlink => {
+                let target = entry.link_name()?.ok_or_else(|| {
+                    anyhow::anyhow!("Symlink {} in archive has no target", path.display())
+                })?;
+                ("120000", target.to_string_lossy().as_bytes().to_vec())
+            }
+            _ => continue,
+        };
+
+        if !tree.insert(&parts, mode, object_id("blob", &content)) {
+            return Err(anyhow::anyhow!(
+                "Templates archive contains conflicting entries for {}",
+                path.display()
+            ));
+        }
+        if TEMPLATE_DIRS.contains(&parts[0].as_str()) {
+            files.push(ArchiveFile {
+                parts,
+                mode,
+                content,
+            });
+        }
+    }
+    Ok((hex::encode(tree.id()), files))
+}
+
+impl ArchiveFile {
+    fn write(&self, dest: &Path) -> Result<()> {
+        let path = self
+            .parts
+            .iter()
+            .fold(dest.to_path_buf(), |path, part| path.join(part));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        #[cfg(unix)]
+        if self.mode == "120000" {
+            let target = std::ffi::OsStr::new(std::str::from_utf8(&self.content)?);
+            std::os::unix::fs::symlink(target, &path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            return Ok(());
+        }
+        fs::write(&path, &self.content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        #[cfg(unix)]
+        if self.mode == "100755" {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+}
+
+/// The id git gives an object of `kind` with `content`.
+fn object_id(kind: &str, content: &[u8]) -> [u8; 20] {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{} {}\0", kind, content.len()).as_bytes());
+    hasher.update(content);
+    hasher.finalize().into()
+}
+
+/// A directory of files, hashed the way git hashes tree objects.
+#[derive(Default)]
+struct Tree {
+    entries: BTreeMap<String, TreeEntry>,
+}
+
+enum TreeEntry {
+    Blob { mode: &'static str, id: [u8; 20] },
+    Tree(Tree),
+}
+
+impl Tree {
+    /// Add a blob at `path`.  Returns false if something is already there,
+    /// or if a parent of `path` is a blob.
+    fn insert(&mut self, path: &[String], mode: &'static str, id: [u8; 20]) -> bool {
+        match path {
+            [] => false,
+            [name] => {
+                if self.entries.contains_key(name) {
+                    return false;
+                }
+                self.entries
+                    .insert(name.clone(), TreeEntry::Blob { mode, id });
+                true
+            }
+            [dir, rest @ ..] => {
+                let entry = self
+                    .entries
+                    .entry(dir.clone())
+                    .or_insert_with(|| TreeEntry::Tree(Tree::default()));
+                match entry {
+                    TreeEntry::Tree(subtree) => subtree.insert(rest, mode, id),
+                    TreeEntry::Blob { .. } => false,
+                }
+            }
+        }
+    }
+
+    fn id(&self) -> [u8; 20] {
+        // git orders entries by name, comparing directories as if their
+        // names ended in '/'.
+        let mut records: Vec<(String, Vec<u8>)> = self
+            .entries
+            .iter()
+            .map(|(name, entry)| {
+                let (mode, id, key) = match entry {
+                    TreeEntry::Blob { mode, id } => (*mode, *id, name.clone()),
+                    TreeEntry::Tree(tree) => ("40000", tree.id(), format!("{}/", name)),
+                };
+                let mut record = format!("{} {}\0", mode, name).into_bytes();
+                record.extend_from_slice(&id);
+                (key, record)
+            })
+            .collect();
+        records.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+        let content: Vec<u8> = records.into_iter().flat_map(|(_, record)| record).collect();
+        object_id("tree", &content)
+    }
+}
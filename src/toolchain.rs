@@ -0,0 +1,489 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::channel::{self, ChannelSpec};
+use crate::commands::common::{self, Source};
+use crate::config::{self, NockupConfig};
+use crate::generation;
+use crate::lock;
+use crate::manifest::ChannelManifest;
+use crate::proxy;
+
+/// Name of the per-project toolchain file, read from the project root next to
+/// `manifest.toml`.
+pub const TOOLCHAIN_FILE: &str = "nockup-toolchain.toml";
+
+/// Binaries provided by every installed toolchain.
+pub const TOOLCHAIN_BINARIES: [&str; 2] = ["hoon", "hoonc"];
+
+/// Environment variable naming the toolchain the proxies should use.
+pub const TOOLCHAIN_ENV: &str = "NOCKUP_TOOLCHAIN";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ToolchainFile {
+    pub toolchain: ToolchainSpec,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ToolchainSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+/// A toolchain resolved to an exact channel and nockchain commit.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub channel: String,
+    pub commit: String,
+}
+
+impl Toolchain {
+    /// Directory name for this toolchain, e.g. `stable-336f744`.
+    pub fn name(&self) -> String {
+        format!(
+            "{}-{}",
+            self.channel,
+            &self.commit[..self.commit.len().min(7)]
+        )
+    }
+
+    pub fn dir(&self) -> Result<PathBuf> {
+        Ok(toolchains_dir()?.join(self.name()))
+    }
+
+    pub fn bin_dir(&self) -> Result<PathBuf> {
+        Ok(self.dir()?.join("bin"))
+    }
+
+    pub fn is_installed(&self) -> Result<bool> {
+        is_installed(&self.name())
+    }
+}
+
+pub fn toolchains_dir() -> Result<PathBuf> {
+    Ok(config::nockup_home()?.join("toolchains"))
+}
+
+/// Whether `name` is installed.  Depending on the profile it was installed
+/// with, a toolchain need not provide every one of `TOOLCHAIN_BINARIES`.
+pub fn is_installed(name: &str) -> Result<bool> {
+    Ok(is_valid_name(name) && toolchains_dir()?.join(name).join("bin").is_dir())
+}
+
+/// Whether `name` can name a directory in `toolchains_dir()`, rather than
+/// a path leading out of it.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// Names of all installed toolchains, sorted.
+pub fn installed_toolchains() -> Result<Vec<String>> {
+    let toolchains_dir = toolchains_dir()?;
+    let mut names = Vec::new();
+    if !toolchains_dir.exists() {
+        return Ok(names);
+    }
+    for entry in std::fs::read_dir(&toolchains_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_installed(&name)? {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// The toolchain that `hoon` and `hoonc` in `~/.nockup/bin` point at.
+pub fn default_toolchain() -> Result<Option<String>> {
+    Ok(NockupConfig::load()
+        .ok()
+        .and_then(|config| config.toolchain))
+}
+
+pub fn validate_commit(commit: &str) -> Result<()> {
+    if commit.len() != 40 || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!(
+            "Invalid commit '{}' (expected a full 40-character commit hash)",
+            commit
+        ));
+    }
+    Ok(())
+}
+
+pub fn load_project_toolchain(project_dir: &Path) -> Result<Option<ToolchainSpec>> {
+    let toolchain_path = project_dir.join(TOOLCHAIN_FILE);
+    if !toolchain_path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&toolchain_path)
+        .with_context(|| format!("Failed to read {}", toolchain_path.display()))?;
+    let file: ToolchainFile = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", toolchain_path.display()))?;
+    let spec = file.toolchain;
+
+    if spec.channel.is_none() && spec.commit.is_none() {
+        return Err(anyhow::anyhow!(
+            "{} must specify a 'channel', a 'commit', or both",
+            TOOLCHAIN_FILE
+        ));
+    }
+    if let Some(channel) = &spec.channel {
+        let channel_spec = ChannelSpec::parse(channel)
+            .map_err(|e| anyhow::anyhow!("Invalid channel in {}: {}", TOOLCHAIN_FILE, e))?;
+        if channel_spec.pin.is_some() && spec.commit.is_some() {
+            return Err(anyhow::anyhow!(
+                "{} may not give a 'commit' when its channel '{}' already pins a build",
+                TOOLCHAIN_FILE,
+                channel
+            ));
+        }
+    }
+    if let Some(commit) = &spec.commit {
+        validate_commit(commit)
+            .map_err(|e| anyhow::anyhow!("Invalid commit in {}: {}", TOOLCHAIN_FILE, e))?;
+    }
+
+    Ok(Some(spec))
+}
+
+/// Resolve the toolchain pinned by `project_dir`, installing it if it is not
+/// present yet.  Returns the pinned toolchain's `bin` directory, or `None` if
+/// the project does not pin a toolchain.
+pub async fn resolve_project_toolchain(project_dir: &Path) -> Result<Option<PathBuf>> {
+    let Some(spec) = load_project_toolchain(project_dir)? else {
+        return Ok(None);
+    };
+
+    let (toolchain, manifest) = resolve_spec(&spec).await?;
+
+    println!(
+        "{} Using toolchain '{}' pinned by {}",
+        "🔧".green(),
+        toolchain.name().cyan(),
+        TOOLCHAIN_FILE
+    );
+
+    if !toolchain.is_installed()? {
+        install_toolchain(&toolchain, manifest, &Source::Remote).await?;
+    }
+
+    Ok(Some(toolchain.bin_dir()?))
+}
+
+/// Resolve a channel spec and optional commit to an exact toolchain.  When no
+/// commit is given the manifest of the build the channel spec selects is
+/// returned alongside it.
+pub async fn resolve_spec(spec: &ToolchainSpec) -> Result<(Toolchain, Option<ChannelManifest>)> {
+    let channel = match &spec.channel {
+        Some(channel) => channel.clone(),
+        None => NockupConfig::load().unwrap_or_default().channel,
+    };
+
+    let channel_spec = ChannelSpec::parse(&channel)?;
+
+    match &spec.commit {
+        Some(commit) => Ok((
+            Toolchain {
+                channel: channel_spec.channel,
+                commit: commit.clone(),
+            },
+            None,
+        )),
+        None => {
+            let manifest = channel_manifest(&channel_spec).await?;
+            let toolchain = toolchain_for_manifest(&channel, &manifest)?;
+            Ok((toolchain, Some(manifest)))
+        }
+    }
+}
+
+/// The toolchain built for `manifest`, fetched for the channel spec `channel`.
+pub fn toolchain_for_manifest(channel: &str, manifest: &ChannelManifest) -> Result<Toolchain> {
+    let base_channel = ChannelSpec::parse(channel)?.channel;
+    let commit = manifest.commit.as_deref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Channel manifest for '{}' does not record a commit; run 'nockup update'",
+            channel
+        )
+    })?;
+    Ok(Toolchain {
+        channel: base_channel,
+        commit: commit.to_string(),
+    })
+}
+
+/// The manifest of the build `spec` selects, from the cache if present.
+async fn channel_manifest(spec: &ChannelSpec) -> Result<ChannelManifest> {
+    let manifest_path = toolchains_dir()?.join(format!("channel-nockup-{}.toml", spec));
+    if manifest_path.exists() {
+        let manifest = common::read_channel_manifest(&manifest_path)?;
+        if manifest.commit.is_some() {
+            return Ok(manifest);
+        }
+    }
+
+    // Pinned builds are only cached once configured as the default channel.
+    if spec.pin.is_some() {
+        let commit = channel::resolve_commit(spec, None).await?;
+        let content = common::fetch_channel_manifest(&spec.channel, &commit).await?;
+        return ChannelManifest::parse(&content);
+    }
+
+    println!(
+        "{} No cached manifest for channel '{}', fetching...",
+        "🔍".yellow(),
+        spec
+    );
+    let _lock = lock::acquire().await?;
+    common::download_toolchain_files(&config::nockup_home()?, &Source::Remote).await?;
+    common::read_channel_manifest(&manifest_path)
+}
+
+/// Install `toolchain` into its own directory, fetching its channel manifest
+/// first if one is not supplied.  The toolchain is staged and verified before
+/// being moved into place, so a failed install leaves nothing behind.
+pub async fn install_toolchain(
+    toolchain: &Toolchain,
+    manifest: Option<ChannelManifest>,
+    source: &Source,
+) -> Result<()> {
+    // Proxies and `nockup build` install pinned toolchains on demand, outside
+    // the commands that lock the home directory up front.
+    let _lock = lock::acquire().await?;
+    let staging_dir = generation::staging_dir()?;
+    let result = async {
+        let staged = stage_toolchain(toolchain, manifest, source, &staging_dir).await?;
+        activate_staged_toolchain(toolchain, &staged)
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&staging_dir);
+    result?;
+
+    println!(
+        "{} Installed toolchain '{}'",
+        "✅".green(),
+        toolchain.name().cyan()
+    );
+    Ok(())
+}
+
+/// Download and verify `toolchain` into `staging_dir`, returning the staged
+/// toolchain directory.  A copy of the manifest is kept alongside the binaries.
+pub async fn stage_toolchain(
+    toolchain: &Toolchain,
+    manifest: Option<ChannelManifest>,
+    source: &Source,
+    staging_dir: &Path,
+) -> Result<PathBuf> {
+    let staged = prepare_staged_toolchain(toolchain, manifest, staging_dir).await?;
+    let packages: Vec<&str> = staged.packages.iter().map(String::as_str).collect();
+    common::install_components(
+        &staged.manifest,
+        &staged.architecture,
+        &packages,
+        &staged.dir.join("bin"),
+        source,
+    )
+    .await?;
+    Ok(staged.dir)
+}
+
+/// A toolchain directory in staging, waiting for its binaries.
+pub struct StagedToolchain {
+    pub dir: PathBuf,
+    pub manifest: ChannelManifest,
+    pub architecture: String,
+    /// Packages of the configured profile that go into `dir/bin`.
+    pub packages: Vec<String>,
+}
+
+/// Create `toolchain`'s directory in `staging_dir` with a copy of its
+/// manifest, fetching the manifest first if one is not supplied.  The
+/// binaries are left for the caller to install, so they can be downloaded
+/// together with others.
+pub async fn prepare_staged_toolchain(
+    toolchain: &Toolchain,
+    manifest: Option<ChannelManifest>,
+    staging_dir: &Path,
+) -> Result<StagedToolchain> {
+    println!(
+        "{} Installing toolchain '{}'...",
+        "⬇️".green(),
+        toolchain.name().cyan()
+    );
+
+    let staged_dir = staging_dir.join("toolchains").join(toolchain.name());
+    std::fs::create_dir_all(&staged_dir)?;
+
+    let manifest = match manifest {
+        Some(manifest) => manifest,
+        None => {
+            let content =
+                common::fetch_channel_manifest(&toolchain.channel, &toolchain.commit).await?;
+            ChannelManifest::parse(&content)?
+        }
+    };
+    std::fs::write(
+        staged_dir.join("manifest.toml"),
+        toml::to_string(&manifest)?,
+    )
+    .context("Failed to write toolchain manifest")?;
+
+    let config = NockupConfig::load().unwrap_or_default();
+    // nockup itself is part of a generation, not of a toolchain.
+    let packages = manifest
+        .profile_packages(config.profile())?
+        .into_iter()
+        .filter(|&package| package != "nockup")
+        .map(String::from)
+        .collect();
+
+    Ok(StagedToolchain {
+        dir: staged_dir,
+        manifest,
+        architecture: config.architecture,
+        packages,
+    })
+}
+
+/// Move a toolchain staged by `stage_toolchain` into place.
+pub fn activate_staged_toolchain(toolchain: &Toolchain, staged_dir: &Path) -> Result<()> {
+    let toolchain_dir = toolchain.dir()?;
+    if toolchain_dir.exists() {
+        // Left over from an install that predates staging.
+        std::fs::remove_dir_all(&toolchain_dir)
+            .with_context(|| format!("Failed to remove {}", toolchain_dir.display()))?;
+    }
+    std::fs::create_dir_all(toolchains_dir()?)?;
+    std::fs::rename(staged_dir, &toolchain_dir)
+        .with_context(|| format!("Failed to install toolchain '{}'", toolchain.name()))
+}
+
+/// Make `name` the default toolchain used by the `hoon` and `hoonc` proxies in
+/// `~/.nockup/bin`.
+pub fn set_default_toolchain(name: &str) -> Result<()> {
+    if !is_installed(name)? {
+        return Err(anyhow::anyhow!("Toolchain '{}' is not installed", name));
+    }
+
+    proxy::install_proxies()?;
+
+    let mut config = NockupConfig::load_or_default()?;
+    config.toolchain = Some(name.to_string());
+    config.save()?;
+    Ok(())
+}
+
+/// Pick the toolchain to run under, in order of precedence: an explicit
+/// `+toolchain` override, `NOCKUP_TOOLCHAIN`, a `nockup-toolchain.toml` in the
+/// current directory or one of its parents, and finally the default toolchain.
+/// Returns the toolchain's name and `bin` directory.
+pub async fn select_toolchain(override_name: Option<&str>) -> Result<(String, PathBuf)> {
+    let requested = match override_name {
+        Some(name) => Some(name.to_string()),
+        None => std::env::var(TOOLCHAIN_ENV)
+            .ok()
+            .filter(|name| !name.is_empty()),
+    };
+    if let Some(requested) = requested {
+        let name = resolve_installed_name(&requested)?;
+        let bin_dir = toolchains_dir()?.join(&name).join("bin");
+        return Ok((name, bin_dir));
+    }
+
+    let cwd = std::env::current_dir()?;
+    if let Some(project_dir) = cwd
+        .ancestors()
+        .find(|dir| dir.join(TOOLCHAIN_FILE).exists())
+    {
+        let spec = load_project_toolchain(project_dir)?
+            .ok_or_else(|| anyhow::anyhow!("Failed to load {}", TOOLCHAIN_FILE))?;
+        let (toolchain, manifest) = resolve_spec(&spec).await?;
+        if !toolchain.is_installed()? {
+            install_toolchain(&toolchain, manifest, &Source::Remote).await?;
+        }
+        return Ok((toolchain.name(), toolchain.bin_dir()?));
+    }
+
+    let name = default_toolchain()?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No default toolchain configured. Run 'nockup install' or 'nockup toolchain default <name>'."
+        )
+    })?;
+    if !is_installed(&name)? {
+        return Err(anyhow::anyhow!(
+            "Default toolchain '{}' is not installed. Run 'nockup toolchain list' to see installed toolchains.",
+            name
+        ));
+    }
+    let bin_dir = toolchains_dir()?.join(&name).join("bin");
+    Ok((name, bin_dir))
+}
+
+/// Resolve a toolchain name or bare channel (e.g. `nightly`) to an installed
+/// toolchain.  A channel resolves to its latest build if that is installed,
+/// otherwise to the most recently installed toolchain from that channel.
+pub fn resolve_installed_name(requested: &str) -> Result<String> {
+    if is_installed(requested)? {
+        return Ok(requested.to_string());
+    }
+
+    let manifest_path = toolchains_dir()?.join(format!("channel-nockup-{}.toml", requested));
+    if manifest_path.exists() {
+        let manifest = common::read_channel_manifest(&manifest_path)?;
+        if let Ok(toolchain) = toolchain_for_manifest(requested, &manifest) {
+            if toolchain.is_installed()? {
+                return Ok(toolchain.name());
+            }
+        }
+    }
+
+    let prefix = format!("{}-", requested);
+    let mut newest: Option<(std::time::SystemTime, String)> = None;
+    for name in installed_toolchains()? {
+        let Some(short) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        if short.len() != 7 || !short.chars().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        let modified = std::fs::metadata(toolchains_dir()?.join(&name))?.modified()?;
+        if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            newest = Some((modified, name));
+        }
+    }
+
+    newest.map(|(_, name)| name).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Toolchain '{}' is not installed. Run 'nockup toolchain install {}'.",
+            requested,
+            requested
+        )
+    })
+}
+
+/// `PATH` with `bin_dir` prepended.
+pub fn prepended_path(bin_dir: &Path) -> Result<std::ffi::OsString> {
+    let mut paths = vec![bin_dir.to_path_buf()];
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+    std::env::join_paths(paths).context("Failed to construct PATH")
+}
+
+/// Prepend `bin_dir` to the `PATH` seen by `command`, so that it and anything
+/// it spawns (e.g. a project's `build.rs`) picks up the pinned toolchain.
+pub fn prepend_to_path(command: &mut tokio::process::Command, bin_dir: &Path) -> Result<()> {
+    command.env("PATH", prepended_path(bin_dir)?);
+    Ok(())
+}
@@ -1,9 +1,11 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use anyhow::{Context, Result};
 use colored::Colorize;
 use tokio::process::Command as TokioCommand;
 
+use crate::config::{self, NockupConfig};
+
 pub async fn show_version_info() -> Result<()> {
     // Show nockup version
     println!("nockup version {}", env!("FULL_VERSION"));
@@ -20,17 +22,20 @@ pub async fn show_version_info() -> Result<()> {
         Err(_) => println!("hoonc  {}", "not found".red()),
     }
 
-    // Get current channel and architecture
-    // The channel is in the TOML file at ~/.nockup/config.toml
-    let config = get_config()?;
-    println!(
-        "current channel {}",
-        config["channel"].as_str().unwrap_or("stable")
-    );
-    println!(
-        "current architecture {}",
-        config["architecture"].as_str().unwrap_or("unknown")
-    );
+    // Get current channel and architecture from config.toml
+    if !config::config_path()?.exists() {
+        println!(
+            "{}",
+            "nockup is not installed; run 'nockup install'".yellow()
+        );
+        return Ok(());
+    }
+    let config = NockupConfig::load()?;
+    println!("current channel {}", config.channel);
+    println!("current architecture {}", config.architecture);
+    if let Some(toolchain) = &config.toolchain {
+        println!("current toolchain {}", toolchain);
+    }
 
     Ok(())
 }
@@ -41,24 +46,11 @@ async fn get_binary_version(binary_name: &str) -> Result<String> {
         which::which(binary_name).context(format!("{} not found in PATH", binary_name))?;
 
     // Verify the binary is the correct architecture
-    let file_output = TokioCommand::new("file")
-        .arg(&binary_path)
-        .output()
-        .await
-        .context("Failed to check binary architecture")?;
-
-    let file_info = String::from_utf8_lossy(&file_output.stdout);
-    let current_arch = std::env::consts::ARCH;
-    let expected_arch = match current_arch {
-        "x86_64" => "x86_64",
-        "aarch64" => "arm64", // macOS uses "arm64" in file output
-        _ => current_arch,
-    };
-
-    if !file_info.contains(expected_arch) {
+    if !matches_host_architecture(&binary_path).await? {
         return Err(anyhow::anyhow!(
             "Binary architecture mismatch for {}: expected {}, found different architecture",
-            binary_name, expected_arch
+            binary_name,
+            expected_file_arch()
         ));
     }
 
@@ -79,17 +71,39 @@ async fn get_binary_version(binary_name: &str) -> Result<String> {
     }
 
     Err(anyhow::anyhow!(
-        "Could not determine {} version - none of the common version flags worked", binary_name
+        "Could not determine {} version - none of the common version flags worked",
+        binary_name
     ))
 }
 
+/// Whether `binary_path` was built for this machine's architecture, according
+/// to `file`.
+pub async fn matches_host_architecture(binary_path: &Path) -> Result<bool> {
+    let file_output = TokioCommand::new("file")
+        .arg(binary_path)
+        .output()
+        .await
+        .context("Failed to check binary architecture")?;
+
+    let file_info = String::from_utf8_lossy(&file_output.stdout);
+    Ok(file_info.contains(expected_file_arch()))
+}
+
+fn expected_file_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x86_64",
+        "aarch64" => "arm64", // macOS uses "arm64" in file output
+        arch => arch,
+    }
+}
+
 fn extract_version_string(version_line: &str) -> String {
     // Try to extract just the version part from output.
     let words: Vec<&str> = version_line.split_whitespace().collect();
 
     // Look for a word that looks like a version (starts with digit or 'v').
     for word in &words {
-        if word.chars().next().map_or(false, |c| c.is_ascii_digit()) {
+        if word.chars().next().is_some_and(|c| c.is_ascii_digit()) {
             return word.to_string();
         }
         if word.starts_with('v') && word.len() > 1 {
@@ -100,16 +114,3 @@ fn extract_version_string(version_line: &str) -> String {
     // Fallback: return the whole line.
     version_line.to_string()
 }
-
-fn get_cache_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-    Ok(home.join(".nockup"))
-}
-
-fn get_config() -> Result<toml::Value> {
-    let cache_dir = get_cache_dir()?;
-    let config_path = cache_dir.join("config.toml");
-    let config_str = std::fs::read_to_string(&config_path)?;
-    let config: toml::Value = toml::de::from_str(&config_str)?;
-    Ok(config)
-}